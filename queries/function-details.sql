@@ -0,0 +1,154 @@
+-- Selects a single function matching the given OID, provided that it is also
+-- an 'available' function, with its arguments & results described the same
+-- way as table columns, so that they can be used with forms & tables alike
+with fn as (
+    select
+        pro.oid,
+        pro.proname,
+        pro.pronargs,
+        pro.proargtypes,
+        pro.proallargtypes,
+        pro.proargmodes,
+        pro.proargnames,
+        pro.proretset,
+        pro.prorettype,
+        pro.provolatile,
+        pro.proisstrict,
+        nsp.nspname,
+        obj_description(pro.oid, 'pg_proc') as description
+
+    from pg_proc      pro
+    join pg_namespace nsp on nsp.oid = pro.pronamespace
+
+    where
+        pro.oid = $3 and
+        concat(nsp.nspname, '.', pro.proname) like any($1)        and
+        not (concat(nsp.nspname, '.', pro.proname) like any($2)) and
+        nsp.nspname = any(current_schemas(false))                and
+        pro.prokind = 'f'                                        and
+        has_function_privilege(pro.oid, 'EXECUTE')               and
+        not exists (
+            select from pg_type t
+            where
+                t.oid = any(pro.proargtypes) and
+                t.typtype = 'p'
+        ) and
+        not exists (
+            select from pg_depend d
+            where
+                d.classid = 'pg_proc'::regclass and
+                d.objid   = pro.oid             and
+                d.deptype = 'e'
+        ) and (
+            -- Like in the tables list, of pseudo-types only `void` and a
+            -- `record` described by output arguments can be returned
+            pro.prorettype = 'void'::regtype or
+            (pro.prorettype = 'record'::regtype and pro.proallargtypes is not null) or
+            not exists (
+                select from pg_type t
+                where
+                    t.oid = pro.prorettype and
+                    t.typtype = 'p'
+            )
+        )
+),
+
+-- Arguments in order, where output (`out`, `inout`, `table`) arguments
+-- describe the results
+params as (
+    select
+        arg.mode in ('i', 'b', 'v') as is_argument,
+        arg.mode in ('o', 'b', 't') as is_result,
+        coalesce(nullif(arg.name, ''), '$' || arg.position) as name,
+        arg.position::integer as position,
+        arg.type,
+        pg_get_function_arg_default(fn.oid, arg.position::integer) as "default"
+
+    from fn, unnest(
+        coalesce(fn.proallargtypes, fn.proargtypes::oid[]),
+        coalesce(fn.proargmodes, array_fill('i'::"char", array[fn.pronargs::integer])),
+        fn.proargnames
+    ) with ordinality as arg(type, mode, name, position)
+
+    union all
+
+    -- Without output arguments, the results are the attributes of a composite
+    -- return type, or the return value itself as a column named after the
+    -- function, like Postgres names it
+    select
+        false,
+        true,
+        coalesce(att.attname, fn.proname),
+        coalesce(att.attnum::integer, 1),
+        coalesce(att.atttypid, fn.prorettype),
+        null
+
+    from      fn
+    join      pg_type      ret on ret.oid = fn.prorettype
+    left join pg_attribute att on att.attrelid = ret.typrelid and att.attnum > 0 and not att.attisdropped
+
+    where
+        not coalesce(fn.proargmodes && array['o', 'b', 't']::"char"[], false) and
+        fn.prorettype <> 'void'::regtype
+),
+
+described as (
+    select
+        params.is_argument,
+        params.is_result,
+        params.position,
+        jsonb_build_object(
+            'checks', '[]'::jsonb,
+            'composite', case when base.typtype = 'c' then base.typrelid::integer end,
+            'data_type', base.typname,
+            'domain', case when typ.typtype = 'd' then typ.typname end,
+            'element_type', elem.typname,
+            'enum_values', (
+                select array_agg(enumlabel order by enumsortorder)
+                from pg_enum
+                where enumtypid = coalesce(elem.oid, base.oid)
+            ),
+            'expression', params.default,
+            'name', params.name,
+            -- Strict functions return null as soon as any argument is null
+            'nullable', not fn.proisstrict,
+            'position', params.position,
+            'range_subtype', (
+                select sub.typname
+                from pg_range rng
+                join pg_type  sub on sub.oid = rng.rngsubtype
+                where rng.rngtypid = coalesce(elem.oid, base.oid)
+            ),
+            'type_schema', bnsp.nspname
+        ) as "column"
+
+    from      fn, params
+    join      pg_type      typ  on typ.oid  = params.type
+    -- Only a single level of domain is resolved, unlike for table columns
+    join      pg_type      base on base.oid = case when typ.typtype = 'd' then typ.typbasetype else typ.oid end
+    join      pg_namespace bnsp on bnsp.oid = base.typnamespace
+    left join pg_type      elem on elem.oid = base.typelem and base.typcategory = 'A'
+)
+
+select
+    fn.oid,
+    fn.proname as "name",
+    fn.nspname as "schema",
+    fn.description,
+    fn.proretset as "returns_set!",
+    case fn.provolatile
+        when 'i' then 'immutable'
+        when 's' then 'stable'
+        else 'volatile'
+    end as "volatility!",
+    coalesce(
+        (select array_agg("column" order by position) from described where is_argument),
+        '{}'
+    ) as "arguments!:Vec<Column>",
+    coalesce(
+        (select array_agg("column" order by position) from described where is_result),
+        '{}'
+    ) as "results!:Vec<Column>"
+
+from fn
+;
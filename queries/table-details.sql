@@ -1,20 +1,45 @@
--- Selects a single table matching the given OID, provided that it
--- is also an 'available' table
+-- Selects a single table (or view, etc.) matching the given OID, provided
+-- that it is also an 'available' table, or (if $4 is true) a composite type
+-- regardless of scope, for the columns using it. Tables (and their columns)
+-- are only available if the current role can select them, and only writable
+-- if it has the privileges to.
 with tbl as (
     select
         cls.oid,
         cls.relname,
-        nsp.nspname
+        nsp.nspname,
+        case cls.relkind
+            when 'v' then 'view'
+            when 'm' then 'materialized view'
+            when 'p' then 'partitioned table'
+            when 'f' then 'foreign table'
+            when 'c' then 'composite type'
+            else 'table'
+        end as kind,
+        -- Views are only writable if they're simple enough, or have rules or
+        -- `instead of` triggers, which Postgres accounts for in this bitmask
+        pg_relation_is_updatable(cls.oid, false) as events,
+        has_any_column_privilege(cls.oid, 'INSERT') as can_insert,
+        has_any_column_privilege(cls.oid, 'UPDATE') as can_update,
+        has_table_privilege(cls.oid, 'DELETE') as can_delete,
+        obj_description(cls.oid, 'pg_class') as description
 
     from pg_class     cls
     join pg_namespace nsp on nsp.oid = cls.relnamespace
 
     where
-        concat(nsp.nspname, '.', cls.relname)     like any($1) and
-        concat(nsp.nspname, '.', cls.relname) not like any($2) and
-        nsp.nspname = any(current_schemas(false))              and
-        cls.relkind = 'r'                                      and
-        cls.oid = $3
+        cls.oid = $3 and (
+            (
+                concat(nsp.nspname, '.', cls.relname) like any($1)        and
+                not (concat(nsp.nspname, '.', cls.relname) like any($2)) and
+                nsp.nspname = any(current_schemas(false))                and
+                cls.relkind in ('r', 'v', 'm', 'p', 'f')                 and
+                not cls.relispartition                                   and
+                has_any_column_privilege(cls.oid, 'SELECT')
+            ) or (
+                cls.relkind = 'c' and $4
+            )
+        )
 
     limit 1 -- Is there any reason to include this?
 )
@@ -25,21 +50,103 @@ select
     tbl.oid,
     tbl.relname as "name",
     tbl.nspname as "schema",
+    tbl.kind as "kind!",
+    tbl.description,
+    tbl.events & 8 = 8 and tbl.can_insert as "insertable!",
+    tbl.events & 4 = 4 and tbl.can_update as "updatable!",
+    tbl.events & 16 = 16 and tbl.can_delete as "deletable!",
 
     array_agg(jsonb_build_object(
+        'checks', checks,
+        'composite', case when typtype = 'c' then typrelid::integer end,
         'data_type', typname,
-        'expression', pg_get_expr(adbin, adrelid),
+        'description', col_description(tbl.oid, attnum),
+        'domain', domname,
+        'element_type', elemname,
+        'enum_values', (
+            select array_agg(enumlabel order by enumsortorder)
+            from pg_enum
+            where enumtypid = coalesce(elemoid, typoid)
+        ),
+        'expression', coalesce(pg_get_expr(adbin, adrelid), domdefault),
         'generated', case
             when attgenerated = 's' then 'stored'
         end,
+        -- Composite types' attributes are only ever written as part of a column
+        'insertable', tbl.kind = 'composite type' or has_column_privilege(tbl.oid, attnum, 'INSERT'),
+        'updatable', tbl.kind = 'composite type' or has_column_privilege(tbl.oid, attnum, 'UPDATE'),
         'identity', case
             when attidentity = 'a' then 'always'
             when attidentity = 'd' then 'default'
         end,
+        'max_length', case
+            when coalesce(elemname, typname) in ('bpchar', 'varchar') and typmod >= 4 then typmod - 4
+        end,
         'name', attname,
-        'nullable', not attnotnull,
-        'position', attnum
-    )) as "columns!:Vec<Column>"
+        'numeric_precision', case
+            when coalesce(elemname, typname) = 'numeric' and typmod >= 4 then ((typmod - 4) >> 16) & 65535
+        end,
+        -- The scale can be negative (eg. `numeric(5, -2)`), so the 11 bits it's
+        -- kept in are sign-extended
+        'numeric_scale', case
+            when coalesce(elemname, typname) = 'numeric' and typmod >= 4 then (((typmod - 4) & 2047) # 1024) - 1024
+        end,
+        'nullable', not attnotnull and not coalesce(domnotnull, false),
+        'position', attnum,
+        'range_subtype', (
+            select sub.typname
+            from pg_range rng
+            join pg_type  sub on sub.oid = rng.rngsubtype
+            where rng.rngtypid = coalesce(elemoid, typoid)
+        ),
+        'references', (
+            select jsonb_build_object(
+                'column', fatt.attname,
+                'table', con.confrelid::integer
+            )
+            from pg_constraint con
+            join pg_attribute  fatt on fatt.attrelid = con.confrelid and fatt.attnum = con.confkey[1]
+            where
+                con.conrelid = tbl.oid and
+                con.contype  = 'f'     and
+                con.conkey   = array[q2.attnum]
+            limit 1
+        ),
+        'type_schema', typnspname
+    )) as "columns!:Vec<Column>",
+    -- Primary key first, then unique constraints, which records can be upserted by
+    coalesce((
+        select jsonb_agg(jsonb_build_object(
+            'name', con.conname,
+            'columns', (
+                select array_agg(att.attname order by key.position)
+                from unnest(con.conkey) with ordinality as key(attnum, position)
+                join pg_attribute att on att.attrelid = con.conrelid and att.attnum = key.attnum
+            )
+        ) order by con.contype, con.conname)
+        from pg_constraint con
+        where
+            con.conrelid = tbl.oid and
+            con.contype in ('p', 'u')
+    ), '[]') as "unique_keys!:Json<Vec<UniqueKey>>",
+    -- Single-column foreign keys of other tables referencing this one, the
+    -- counterpart of each column's `references`
+    coalesce((
+        select jsonb_agg(jsonb_build_object(
+            'table', con.conrelid::integer,
+            'column', att.attname,
+            'references', fatt.attname
+        ) order by con.conrelid, att.attname)
+        from pg_constraint con
+        join pg_class      cls  on cls.oid       = con.conrelid
+        join pg_attribute  att  on att.attrelid  = con.conrelid  and att.attnum  = con.conkey[1]
+        join pg_attribute  fatt on fatt.attrelid = con.confrelid and fatt.attnum = con.confkey[1]
+        where
+            con.confrelid = tbl.oid and
+            con.contype   = 'f'     and
+            cardinality(con.conkey) = 1 and
+            not cls.relispartition -- partitions inherit their parent's keys
+    ), '[]') as "referenced_by!:Json<Vec<Referrer>>"
 
 from tbl
 join (
@@ -49,7 +156,18 @@ join (
         att.attnotnull,
         att.attidentity,
         att.attgenerated,
-        typ.typname,
+        base.oid as typoid,
+        base.typname,
+        base.typtype,
+        base.typrelid,
+        bnsp.nspname as typnspname,
+        elem.oid as elemoid,
+        elem.typname as elemname,
+        coalesce(dom.typmod, att.atttypmod) as typmod,
+        case when dom.oids is not null then typ.typname end as domname,
+        dom.notnull as domnotnull,
+        dom.default as domdefault,
+        coalesce(dom.checks, '[]') as checks,
         def.adbin,
         def.adrelid
 
@@ -58,9 +176,56 @@ join (
     join      pg_type      typ on typ.oid      = att.atttypid
     left join pg_attrdef   def on def.adrelid  = tbl.oid and def.adnum = att.attnum
 
+    -- Domains can be defined over other domains, so walk the chain down to
+    -- the underlying base type, collecting constraints & defaults on the way
+    left join lateral (
+        with recursive domains as (
+            select
+                typ.oid,
+                typ.typbasetype,
+                typ.typtypmod,
+                typ.typnotnull,
+                typ.typdefault,
+                1 as depth
+            where typ.typtype = 'd'
+
+            union all
+
+            select
+                t.oid,
+                t.typbasetype,
+                t.typtypmod,
+                t.typnotnull,
+                t.typdefault,
+                d.depth + 1
+            from domains d
+            join pg_type t on t.oid = d.typbasetype
+            where t.typtype = 'd'
+        )
+        select
+            array_agg(oid) as oids,
+            (array_agg(typbasetype order by depth desc))[1] as base_oid,
+            (array_agg(typtypmod order by depth) filter (where typtypmod >= 0))[1] as typmod,
+            (array_agg(typdefault order by depth) filter (where typdefault is not null))[1] as "default",
+            bool_or(typnotnull) as notnull,
+            (
+                select jsonb_agg(jsonb_build_object(
+                    'definition', pg_get_constraintdef(con.oid),
+                    'name', con.conname
+                ))
+                from pg_constraint con
+                where con.contypid = any(array_agg(domains.oid))
+            ) as checks
+        from domains
+    ) dom on true
+    join pg_type base on base.oid = coalesce(dom.base_oid, typ.oid)
+    join pg_namespace bnsp on bnsp.oid = base.typnamespace
+    left join pg_type elem on elem.oid = base.typelem and base.typcategory = 'A'
+
     where
         not att.attisdropped and
-        att.attnum > 0
+        att.attnum > 0       and
+        (tbl.kind = 'composite type' or has_column_privilege(tbl.oid, att.attnum, 'SELECT'))
 
     order by att.attnum
 ) q2 on true
@@ -68,6 +233,11 @@ join (
 group by
     tbl.oid,
     tbl.relname,
-    tbl.nspname
+    tbl.nspname,
+    tbl.kind,
+    tbl.events,
+    tbl.can_insert,
+    tbl.can_update,
+    tbl.can_delete,
+    tbl.description
 ;
-
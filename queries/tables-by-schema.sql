@@ -1,11 +1,19 @@
 select
-    nspname as name,
-    array_agg("table") as "tables!:Vec<SchemaTable>"
+    nspname as "name!",
+    array_agg("table" order by is_function, "table"->>'name') as "tables!:Vec<SchemaTable>"
 
 from (
     select
         n.nspname,
+        false as is_function,
         jsonb_build_object(
+          'kind', case c.relkind
+            when 'v' then 'view'
+            when 'm' then 'materialized view'
+            when 'p' then 'partitioned table'
+            when 'f' then 'foreign table'
+            else 'table'
+          end,
           'name', c.relname,
           'oid', c.oid::integer
         ) as "table"
@@ -16,13 +24,61 @@ from (
     where
         -- Should this be optimized a bit..?
         concat(n.nspname, '.', c.relname) like any($1) and
-        concat(n.nspname, '.', c.relname) not like any($2) and
+        not (concat(n.nspname, '.', c.relname) like any($2)) and
         n.nspname = any(current_schemas(false)) and -- exclude implicit schemas
-        c.relkind = 'r'
+        c.relkind in ('r', 'v', 'm', 'p', 'f') and
+        not c.relispartition and -- partitions are edited through their parent
+        has_any_column_privilege(c.oid, 'SELECT')
 
-    order by n.nspname, c.relname
+    union all
+
+    -- Functions are listed alongside tables as "actions", but only those that
+    -- can be called from a form, ie. whose arguments & results have actual types
+    select
+        n.nspname,
+        true as is_function,
+        jsonb_build_object(
+          'kind', 'function',
+          'name', p.proname,
+          'oid', p.oid::integer
+        ) as "table"
+
+    from pg_proc p
+    join pg_namespace n on n.oid = p.pronamespace
+
+    where
+        concat(n.nspname, '.', p.proname) like any($3) and
+        not (concat(n.nspname, '.', p.proname) like any($4)) and
+        n.nspname = any(current_schemas(false)) and
+        p.prokind = 'f' and
+        has_function_privilege(p.oid, 'EXECUTE') and
+        not exists (
+            select from pg_type t
+            where
+                t.oid = any(p.proargtypes) and
+                t.typtype = 'p'
+        ) and
+        not exists (
+            -- Functions belonging to extensions are better left alone
+            select from pg_depend d
+            where
+                d.classid = 'pg_proc'::regclass and
+                d.objid   = p.oid               and
+                d.deptype = 'e'
+        ) and (
+            -- Of pseudo-types, only `void` and a `record` described by output
+            -- arguments can be returned usefully
+            p.prorettype = 'void'::regtype or
+            (p.prorettype = 'record'::regtype and p.proallargtypes is not null) or
+            not exists (
+                select from pg_type t
+                where
+                    t.oid = p.prorettype and
+                    t.typtype = 'p'
+            )
+        )
 ) q
 
 group by nspname
+order by nspname
 ;
-
@@ -0,0 +1,211 @@
+//! The JSON API, which mirrors the records routes with records as JSON objects
+//! keyed by column name. Values are passed through in the same text format the
+//! forms use, except where JSON has a natural equivalent: `null`, numbers,
+//! booleans, arrays, composites as objects, and `json`/`jsonb` columns as is.
+
+pub mod openapi;
+
+use crate::{db::{self, literal}, ui::form::{self, Value}};
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue};
+use sqlx::postgres::PgDatabaseError;
+use std::fmt;
+
+/// Records per page when listing records, unless chosen
+pub const PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+#[derive(Debug, Serialize)]
+pub struct Page {
+    pub records: Vec<JsonValue>,
+    /// Records matching the filters, across all pages
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+/// An error as returned to clients, where constraint violations name the
+/// constraint (and column, if any) so they can be handled programmatically
+#[derive(Debug, Default, Serialize)]
+pub struct Error {
+    #[serde(skip)]
+    pub status: u16,
+    pub message: String,
+    /// Why the database rejected the statement, if it did
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub database: Option<Box<DatabaseError>>,
+    /// Values rejected before reaching the database
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatabaseError {
+    /// The `SQLSTATE` error code
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constraint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub column: String,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(status: u16, message: &str) -> Self {
+        Self { status, message: message.to_owned(), ..Default::default() }
+    }
+
+    pub fn not_found() -> Self {
+        Self::new(404, "Not found")
+    }
+
+    pub fn read_only() -> Self {
+        Self::new(405, "This relation is read-only")
+    }
+
+    pub fn invalid(fields: Vec<(String, String)>) -> Self {
+        Self {
+            fields: fields.into_iter()
+                .map(|(column, message)| FieldError { column, message })
+                .collect(),
+            ..Self::new(422, "Invalid values")
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(serde_json::json!({ "error": self }))
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        let e = match error {
+            sqlx::Error::RowNotFound => return Self::not_found(),
+            sqlx::Error::Database(e) => e,
+            e => return Self::new(500, &e.to_string()),
+        };
+
+        let code = e.code().map(|c| c.into_owned());
+
+        // Unique, exclusion & foreign key violations conflict with other records,
+        // whereas other integrity violations and invalid input are the request's own fault
+        let status = match code.as_deref() {
+            Some("23505" | "23P01" | "23503") => 409,
+            Some("42501") => 403,
+            Some(code) if code.starts_with("23") || code.starts_with("22") => 422,
+            _ => 500,
+        };
+
+        let pg_error = e.try_downcast_ref::<PgDatabaseError>();
+
+        Self {
+            status,
+            message: e.message().to_owned(),
+            database: Some(Box::new(DatabaseError {
+                constraint: e.constraint().map(|c| c.to_owned()),
+                column: pg_error.and_then(|e| e.column()).map(|c| c.to_owned()),
+                detail: pg_error.and_then(|e| e.detail()).map(|d| d.to_owned()),
+                code,
+            })),
+            fields: Vec::new(),
+        }
+    }
+}
+
+impl From<form::Error> for Error {
+    fn from(error: form::Error) -> Self {
+        match error {
+            form::Error::Sql(e) => e.into(),
+            form::Error::Invalid(fields) => Self::invalid(fields),
+        }
+    }
+}
+
+/// Pairs the columns in a submitted record with their values, rejecting
+/// columns the table doesn't have. Columns the database always generates are
+/// ignored, so that records can be sent back the way they were received.
+pub fn values(table: &db::Table, record: &Map<String, JsonValue>) -> Result<Vec<(String, Value)>, Error> {
+    let mut values = Vec::new();
+    let mut invalid = Vec::new();
+
+    for (key, value) in record {
+        let column = match table.columns.iter().find(|c| &c.name == key) {
+            Some(column) if column.always_generated() => continue,
+            Some(column) => column,
+            None => {
+                invalid.push((key.to_owned(), "There's no such column".to_owned()));
+                continue;
+            }
+        };
+
+        match value_of(column, value) {
+            Ok(value) => values.push((key.to_owned(), value)),
+            Err(message) => invalid.push((key.to_owned(), message)),
+        }
+    }
+
+    match invalid.is_empty() {
+        true => Ok(values),
+        false => Err(Error::invalid(invalid)),
+    }
+}
+
+fn value_of(column: &db::Column, value: &JsonValue) -> Result<Value, String> {
+    Ok(match value {
+        JsonValue::Null => Value::Null,
+        value if column.is_json() => Value::Text(value.to_string()),
+        JsonValue::Array(elements) if column.element_type.is_some() => {
+            let elements = elements.iter()
+                .map(|e| scalar(e).map(Some).ok_or_else(|| "Array elements must be strings, numbers or booleans".to_owned()))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Value::Text(literal::encode_array(&elements))
+        }
+        JsonValue::Object(fields) if !column.attributes.is_empty() => {
+            if let Some(key) = fields.keys().find(|k| !column.attributes.iter().any(|a| &&a.name == k)) {
+                return Err(format!("There's no attribute {key}"));
+            }
+
+            // Attributes may themselves be arrays or composites
+            let fields = column.attributes.iter()
+                .map(|a| match fields.get(&a.name).map(|f| value_of(a, f)).transpose()? {
+                    Some(Value::Text(text)) => Ok(Some(text)),
+                    _ => Ok(None),
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+
+            Value::Text(literal::encode_row(&fields.iter().map(|f| f.as_deref()).collect::<Vec<_>>()))
+        }
+        value => Value::Text(scalar(value).ok_or_else(|| "Expected a string, number or boolean".to_owned())?),
+    })
+}
+
+/// The text of a string, number or boolean
+fn scalar(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(text) => Some(text.to_owned()),
+        JsonValue::Number(number) => Some(number.to_string()),
+        JsonValue::Bool(boolean) => Some(boolean.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,325 @@
+//! An OpenAPI document describing the JSON API, generated from the tables'
+//! metadata, with a schema describing each table's records as JSON. Which of
+//! its properties are required depends on the route, so that's left to them.
+
+use crate::{api, db, ui, Config};
+use serde_json::{json, Map, Value};
+
+/// Describes the records routes of each table, where records are only
+/// addressable by `id` if tables have one
+pub fn document(tables: &[db::Table], config: &Config) -> Value {
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+
+    for table in tables {
+        let name = schema_name(table);
+        let reference = json!({ "$ref": format!("#/components/schemas/{name}") });
+
+        // Records as returned always have every column, even if null
+        let columns = table.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+        let record = requiring(&reference, &columns);
+
+        paths.insert(format!("/api/tables/{}/records", table.oid.0), records_path(table, &name, &reference, &record));
+
+        // TODO: Don't rely on `id` field
+        if table.columns.iter().any(|c| c.name == "id") {
+            paths.insert(format!("/api/tables/{}/records/{{record_id}}", table.oid.0), record_path(table, &name, &reference, &record));
+        }
+
+        schemas.insert(name, record_schema(table, config));
+    }
+
+    schemas.insert("Error".to_owned(), error_schema());
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "mngr",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "responses": {
+                "Error": {
+                    "description": "The request was rejected, eg. for violating a constraint",
+                    "content": {
+                        "application/json": {
+                            "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "error": { "$ref": "#/components/schemas/Error" },
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+/// Schema names may only contain letters, digits, `.`, `-` and `_`
+fn schema_name(table: &db::Table) -> String {
+    format!("{}.{}", table.schema, table.name)
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+fn records_path(table: &db::Table, name: &str, reference: &Value, record: &Value) -> Value {
+    let columns = table.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+
+    // Columns that must be given when creating a record
+    let required = table.columns.iter()
+        .filter(|c| !c.nullable && !c.has_default() && !c.is_generated())
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+
+    let mut parameters = vec![
+        json!({ "name": "page", "in": "query", "schema": { "type": "integer", "minimum": 1, "default": 1 } }),
+        json!({
+            "name": "per_page",
+            "in": "query",
+            "schema": { "type": "integer", "minimum": 1, "maximum": api::MAX_PAGE_SIZE, "default": api::PAGE_SIZE },
+        }),
+        json!({ "name": "sort_column", "in": "query", "schema": { "type": "string", "enum": columns } }),
+        json!({ "name": "sort_direction", "in": "query", "schema": { "type": "string", "enum": ["asc", "desc"] } }),
+    ];
+
+    parameters.extend(table.columns.iter().map(|c| json!({
+        "name": format!("{}{}", ui::table::FILTER_PREFIX, c.name),
+        "in": "query",
+        "description": format!("Only records whose {} is exactly this", c.name),
+        "schema": { "type": "string" },
+    })));
+
+    let mut path = json!({
+        "get": {
+            "operationId": format!("list.{name}"),
+            "tags": [name],
+            "parameters": parameters,
+            "responses": {
+                "200": {
+                    "description": "A page of records",
+                    "content": {
+                        "application/json": {
+                            "schema": {
+                                "type": "object",
+                                "required": ["records", "total", "page", "per_page"],
+                                "properties": {
+                                    "records": { "type": "array", "items": record },
+                                    "total": { "type": "integer" },
+                                    "page": { "type": "integer" },
+                                    "per_page": { "type": "integer" },
+                                },
+                            },
+                        },
+                    },
+                },
+                "default": { "$ref": "#/components/responses/Error" },
+            },
+        },
+    });
+
+    if table.insertable {
+        path["post"] = json!({
+            "operationId": format!("create.{name}"),
+            "tags": [name],
+            "description": "Columns left out are left to their default",
+            "requestBody": {
+                "required": true,
+                "content": {
+                    "application/json": {
+                        "schema": requiring(reference, &required),
+                    },
+                },
+            },
+            "responses": {
+                "201": record_response("The new record", record),
+                "default": { "$ref": "#/components/responses/Error" },
+            },
+        });
+    }
+
+    path
+}
+
+fn record_path(table: &db::Table, name: &str, reference: &Value, record: &Value) -> Value {
+    let mut path = json!({
+        "parameters": [
+            { "name": "record_id", "in": "path", "required": true, "schema": { "type": "integer", "format": "int64" } },
+        ],
+        "get": {
+            "operationId": format!("get.{name}"),
+            "tags": [name],
+            "responses": {
+                "200": record_response("The record", record),
+                "default": { "$ref": "#/components/responses/Error" },
+            },
+        },
+    });
+
+    if table.updatable {
+        path["patch"] = json!({
+            "operationId": format!("update.{name}"),
+            "tags": [name],
+            "description": "Columns left out are left as they are",
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": reference } },
+            },
+            "responses": {
+                "200": record_response("The updated record", record),
+                "default": { "$ref": "#/components/responses/Error" },
+            },
+        });
+    }
+
+    if table.deletable {
+        path["delete"] = json!({
+            "operationId": format!("delete.{name}"),
+            "tags": [name],
+            "responses": {
+                "204": { "description": "The record was deleted" },
+                "default": { "$ref": "#/components/responses/Error" },
+            },
+        });
+    }
+
+    path
+}
+
+/// A schema requiring the given properties, which can't be an empty list
+fn requiring(reference: &Value, required: &[&str]) -> Value {
+    match required.is_empty() {
+        true => reference.clone(),
+        false => json!({ "allOf": [reference, { "required": required }] }),
+    }
+}
+
+fn record_response(description: &str, record: &Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": record } },
+    })
+}
+
+/// A table's records as JSON objects, with column labels as titles and
+/// comments as descriptions
+fn record_schema(table: &db::Table, config: &Config) -> Value {
+    let table_config = config.table(&table.schema, &table.name);
+
+    let properties = table.columns.iter()
+        .map(|column| {
+            let mut schema = column_schema(column, table_config.and_then(|t| t.json_schema(&column.name)));
+
+            if let Some(label) = table_config.and_then(|t| t.label(&column.name)) {
+                schema["title"] = json!(label);
+            }
+
+            (column.name.clone(), schema)
+        })
+        .collect::<Map<_, _>>();
+
+    let mut schema = json!({
+        "type": "object",
+        "title": table.name,
+        "properties": properties,
+    });
+
+    if let Some(description) = table_config.and_then(|t| t.description.as_ref()).or(table.description.as_ref()) {
+        schema["description"] = json!(description);
+    }
+
+    schema
+}
+
+/// A column's values as JSON, the way Postgres' `to_jsonb` writes them
+fn column_schema(column: &db::Column, json_schema: Option<Value>) -> Value {
+    let mut schema = match (&column.element_type, column.is_json()) {
+        (_, true) => json_schema.unwrap_or_else(|| json!({})),
+        (Some(element_type), _) => json!({
+            "type": "array",
+            "items": type_schema(element_type, column),
+        }),
+        (None, _) => type_schema(&column.data_type, column),
+    };
+
+    if column.nullable {
+        schema["nullable"] = json!(true);
+    }
+
+    if column.always_generated() || !(column.insertable || column.updatable) {
+        schema["readOnly"] = json!(true);
+    }
+
+    if let Some(description) = &column.description {
+        schema["description"] = json!(description);
+    }
+
+    schema
+}
+
+/// A single value of the given type, which is the column's own type or that
+/// of its array's elements
+fn type_schema(type_name: &str, column: &db::Column) -> Value {
+    if let Some(enum_values) = &column.enum_values {
+        return json!({ "type": "string", "enum": enum_values });
+    }
+
+    if !column.attributes.is_empty() {
+        let properties = column.attributes.iter()
+            .map(|a| (a.name.clone(), column_schema(a, None)))
+            .collect::<Map<_, _>>();
+
+        return json!({ "type": "object", "properties": properties });
+    }
+
+    match type_name {
+        "int2" | "int4" => json!({ "type": "integer", "format": "int32" }),
+        "int8" => json!({ "type": "integer", "format": "int64" }),
+        "float4" => json!({ "type": "number", "format": "float" }),
+        "float8" => json!({ "type": "number", "format": "double" }),
+        "numeric" => json!({ "type": "number" }),
+        "bool" => json!({ "type": "boolean" }),
+        "date" => json!({ "type": "string", "format": "date" }),
+        "timestamptz" => json!({ "type": "string", "format": "date-time" }),
+        "uuid" => json!({ "type": "string", "format": "uuid" }),
+        "bytea" => json!({ "type": "string", "pattern": "^\\\\x([0-9a-f]{2})*$" }),
+        "bpchar" | "varchar" => match column.max_length {
+            Some(max_length) => json!({ "type": "string", "maxLength": max_length }),
+            None => json!({ "type": "string" }),
+        },
+        _ => json!({ "type": "string" }),
+    }
+}
+
+fn error_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["message"],
+        "properties": {
+            "message": { "type": "string" },
+            "code": { "type": "string", "description": "The SQLSTATE error code, if the database rejected the statement" },
+            "constraint": { "type": "string" },
+            "column": { "type": "string" },
+            "detail": { "type": "string" },
+            "fields": {
+                "type": "array",
+                "description": "Values rejected before reaching the database",
+                "items": {
+                    "type": "object",
+                    "required": ["column", "message"],
+                    "properties": {
+                        "column": { "type": "string" },
+                        "message": { "type": "string" },
+                    },
+                },
+            },
+        },
+    })
+}
@@ -0,0 +1,238 @@
+//! Logging in as one of the users defined in the config. Sessions are kept in
+//! memory, so everyone logs in again after a restart, and are identified by a
+//! random token in a cookie. Files uploaded to be imported are kept with the
+//! session too, until they're run.
+
+use crate::Config;
+use argon2::Argon2;
+use password_hash::{
+    rand_core::{OsRng, RngCore},
+    PasswordHash,
+    PasswordHasher,
+    PasswordVerifier,
+    SaltString,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+pub const SESSION_COOKIE: &str = "session";
+
+/// The most uploads kept for each session, dropping the oldest beyond that
+const MAX_UPLOADS: usize = 3;
+
+/// The logged in user, as added to each request's extensions
+#[derive(Clone, Debug)]
+pub struct User {
+    pub name: String,
+    /// The Postgres role the user's requests act as, if any
+    pub role: Option<String>,
+    /// The role in the config limiting what the user can do, if any
+    pub permissions: Option<String>,
+    /// The configured session variables, as (setting, value) pairs, where
+    /// attributes the user doesn't have are empty
+    pub settings: Vec<(String, String)>,
+}
+
+struct Session {
+    user: User,
+    expires: Instant,
+    /// Uploaded files' contents by token, oldest first
+    uploads: Vec<(String, String)>,
+}
+
+/// Sessions by token, shared between workers
+#[derive(Clone, Default)]
+pub struct Sessions(Arc<Mutex<HashMap<String, Session>>>);
+
+// Tokens are as good as passwords, so they're left out of `/debug/state`
+impl fmt::Debug for Sessions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Sessions")
+            .field(&self.0.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl Sessions {
+    /// Starts a session for the user, returning its token
+    pub fn start(&self, user: User, max_age: Duration) -> String {
+        let token = random_token();
+        let mut sessions = self.0.lock().unwrap();
+
+        // Sessions that were never ended are only cleaned up here
+        let now = Instant::now();
+        sessions.retain(|_, s| s.expires > now);
+        sessions.insert(token.clone(), Session { user, expires: now + max_age, uploads: Vec::new() });
+
+        token
+    }
+
+    /// The user a token belongs to, unless their session expired or ended
+    pub fn user(&self, token: &str) -> Option<User> {
+        self.0.lock().unwrap()
+            .get(token)
+            .filter(|s| s.expires > Instant::now())
+            .map(|s| s.user.clone())
+    }
+
+    pub fn end(&self, token: &str) {
+        self.0.lock().unwrap().remove(token);
+    }
+
+    /// Keeps an uploaded file's contents with a session, returning the token
+    /// it can be read back by, unless the session expired or ended
+    pub fn keep_upload(&self, token: &str, data: String) -> Option<String> {
+        let mut sessions = self.0.lock().unwrap();
+        let session = sessions.get_mut(token).filter(|s| s.expires > Instant::now())?;
+        let upload = random_token();
+
+        if session.uploads.len() >= MAX_UPLOADS {
+            session.uploads.remove(0);
+        }
+
+        session.uploads.push((upload.clone(), data));
+
+        Some(upload)
+    }
+
+    /// The contents of a file uploaded within a session, if it's still kept
+    pub fn upload(&self, token: &str, upload: &str) -> Option<String> {
+        self.0.lock().unwrap()
+            .get(token)
+            .filter(|s| s.expires > Instant::now())?
+            .uploads.iter()
+            .find(|(u, _)| u == upload)
+            .map(|(_, data)| data.clone())
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Checks a user's password, taking about as long for users that don't exist
+/// so that response times don't give away who does
+pub fn authenticate(config: &Config, name: &str, password: &str) -> Option<User> {
+    let argon2 = Argon2::default();
+
+    let user = match config.user(name) {
+        Some(user) => user,
+        None => {
+            let _ = hash_password(password);
+            return None;
+        }
+    };
+
+    let hash = PasswordHash::new(&user.password_hash).ok()?;
+
+    argon2.verify_password(password.as_bytes(), &hash).ok()?;
+
+    let mut settings = config.session_variables.iter()
+        .flatten()
+        .map(|(setting, attribute)| (setting.clone(), user.attribute(attribute).unwrap_or_default().to_owned()))
+        .collect::<Vec<_>>();
+    settings.sort();
+
+    Some(User {
+        name: user.name.clone(),
+        role: user.role.clone(),
+        permissions: user.permissions.clone(),
+        settings,
+    })
+}
+
+/// Hashes a password for a user's `password_hash` in the config
+pub fn hash_password(password: &str) -> Result<String, password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Ok(Argon2::default().hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> User {
+        User { name: "someone".to_owned(), role: None, permissions: None, settings: Vec::new() }
+    }
+
+    #[test]
+    fn sessions_last_until_they_expire() {
+        let sessions = Sessions::default();
+        let token = sessions.start(user(), Duration::from_secs(60));
+
+        assert_eq!(sessions.user(&token).map(|u| u.name), Some("someone".to_owned()));
+        assert!(sessions.user("unknown").is_none());
+
+        let expired = sessions.start(user(), Duration::ZERO);
+
+        assert!(sessions.user(&expired).is_none());
+    }
+
+    #[test]
+    fn sessions_can_end() {
+        let sessions = Sessions::default();
+        let token = sessions.start(user(), Duration::from_secs(60));
+
+        sessions.end(&token);
+
+        assert!(sessions.user(&token).is_none());
+    }
+
+    #[test]
+    fn expired_sessions_are_cleaned_up() {
+        let sessions = Sessions::default();
+
+        sessions.start(user(), Duration::ZERO);
+        sessions.start(user(), Duration::from_secs(60));
+
+        assert_eq!(sessions.0.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn sessions_have_unique_tokens() {
+        let sessions = Sessions::default();
+        let first = sessions.start(user(), Duration::from_secs(60));
+        let second = sessions.start(user(), Duration::from_secs(60));
+
+        assert_ne!(first, second);
+        assert_eq!(first.len(), 64);
+    }
+
+    #[test]
+    fn uploads_are_kept_with_their_session() {
+        let sessions = Sessions::default();
+        let token = sessions.start(user(), Duration::from_secs(60));
+        let other = sessions.start(user(), Duration::from_secs(60));
+
+        let upload = sessions.keep_upload(&token, "a,b".to_owned()).unwrap();
+
+        assert_eq!(sessions.upload(&token, &upload).as_deref(), Some("a,b"));
+        assert_eq!(sessions.upload(&other, &upload), None);
+        assert_eq!(sessions.keep_upload("unknown", "a,b".to_owned()), None);
+
+        sessions.end(&token);
+
+        assert_eq!(sessions.upload(&token, &upload), None);
+    }
+
+    #[test]
+    fn only_the_latest_uploads_are_kept() {
+        let sessions = Sessions::default();
+        let token = sessions.start(user(), Duration::from_secs(60));
+
+        let uploads = (0..=MAX_UPLOADS)
+            .map(|i| sessions.keep_upload(&token, i.to_string()).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(sessions.upload(&token, &uploads[0]), None);
+        assert_eq!(sessions.upload(&token, &uploads[MAX_UPLOADS]), Some(MAX_UPLOADS.to_string()));
+    }
+}
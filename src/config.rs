@@ -1,5 +1,5 @@
 use serde::Deserialize;
-use std::fs;
+use std::{collections::HashMap, fmt, fs};
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ScopeConfig {
@@ -7,6 +7,17 @@ pub struct ScopeConfig {
     pub exclude: Vec<String>,
 }
 
+/// A statement run against selected records of a table, eg. calling a function
+/// with each record's primary key
+#[derive(Clone, Debug, Deserialize)]
+pub struct ActionConfig {
+    pub name: String,
+    /// Run once per record, where `$pk` is replaced with the record's primary key
+    pub sql: String,
+    /// Asked before running the action, if set
+    pub confirm: Option<String>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct TableConfig {
     // TODO: If this is optional, there needs to be an error at application
@@ -16,17 +27,235 @@ pub struct TableConfig {
     pub table: String,
     pub description: Option<String>,
     pub lookup: Option<Vec<String>>,
+    /// JSON Schemas that documents submitted for `json`/`jsonb` columns must
+    /// match, keyed by column name and written as TOML tables
+    pub json_schemas: Option<HashMap<String, toml::Value>>,
+    pub actions: Option<Vec<ActionConfig>>,
+    /// Human-friendly names for columns, keyed by column name, eg. for the
+    /// header row of exported files
+    pub labels: Option<HashMap<String, String>>,
+}
+
+impl TableConfig {
+    pub fn json_schema(&self, column: &str) -> Option<serde_json::Value> {
+        let schema = self.json_schemas.as_ref()?.get(column)?;
+
+        Some(serde_json::to_value(schema).unwrap())
+    }
+
+    pub fn label(&self, column: &str) -> Option<&str> {
+        self.labels.as_ref()?.get(column).map(|l| l.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct UploadsConfig {
+    /// Largest file, in bytes, that can be uploaded into a `bytea` column or
+    /// imported into a table
+    pub max_size: usize,
+}
+
+impl Default for UploadsConfig {
+    fn default() -> Self {
+        Self { max_size: 10 * 1024 * 1024 }
+    }
+}
+
+/// Someone who can log in, with their password hashed by `mngr hash-password`
+#[derive(Clone, Deserialize)]
+pub struct UserConfig {
+    pub name: String,
+    /// An Argon2 hash in the PHC string format, eg. `$argon2id$v=19$...`
+    pub password_hash: String,
+    /// The Postgres role to act as, which the user in `DATABASE_URL` must be
+    /// a member of; if left out, the user acts as the `DATABASE_URL` user
+    pub role: Option<String>,
+    /// Anything else about the user to pass along in `session_variables`, eg. their id
+    pub attributes: Option<HashMap<String, String>>,
+    /// The name of the `roles` entry limiting what the user can do, on top of
+    /// what the database allows; if left out, the user isn't limited any further
+    pub permissions: Option<String>,
+}
+
+// Hashes can be cracked offline, so they're left out of `/debug/state`
+impl fmt::Debug for UserConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserConfig")
+            .field("name", &self.name)
+            .field("password_hash", &"<redacted>")
+            .field("role", &self.role)
+            .field("attributes", &self.attributes)
+            .field("permissions", &self.permissions)
+            .finish()
+    }
+}
+
+impl UserConfig {
+    /// The user's `name`, `role` or one of their other attributes
+    pub fn attribute(&self, attribute: &str) -> Option<&str> {
+        match attribute {
+            "name" => Some(&self.name),
+            "role" => self.role.as_deref(),
+            _ => self.attributes.as_ref()?.get(attribute).map(|a| a.as_str()),
+        }
+    }
+}
+
+/// What a role can do with a table's records
+#[derive(Clone, Copy, Debug)]
+pub struct Rights {
+    pub read: bool,
+    pub create: bool,
+    pub update: bool,
+    pub delete: bool,
+}
+
+/// A role's rights on a table, where rights left out are the role's own, and
+/// the columns it can't see or can't change
+#[derive(Clone, Debug, Deserialize)]
+pub struct RoleTableConfig {
+    pub schema: Option<String>,
+    pub table: String,
+    pub read: Option<bool>,
+    pub create: Option<bool>,
+    pub update: Option<bool>,
+    pub delete: Option<bool>,
+    pub hidden: Option<Vec<String>>,
+    pub readonly: Option<Vec<String>>,
+}
+
+/// Permissions defined in mngr rather than the database, eg. so that support
+/// staff get a narrower view than others, even through the same database login
+#[derive(Clone, Debug, Deserialize)]
+pub struct RoleConfig {
+    pub name: String,
+    /// Rights on tables that aren't listed in `tables`, where there are none
+    /// unless given
+    #[serde(default)]
+    pub read: bool,
+    #[serde(default)]
+    pub create: bool,
+    #[serde(default)]
+    pub update: bool,
+    #[serde(default)]
+    pub delete: bool,
+    /// Whether functions can be called, which can do anything the database allows
+    #[serde(default)]
+    pub functions: bool,
+    pub tables: Option<Vec<RoleTableConfig>>,
+}
+
+impl RoleConfig {
+    /// Finds the role's rules for a table, matched the same way as `tables`
+    pub fn table(&self, schema: &str, table: &str) -> Option<&RoleTableConfig> {
+        self.tables.as_ref()?.iter().find(|t| {
+            t.table == table && t.schema.as_deref().is_none_or(|s| s == schema)
+        })
+    }
+
+    pub fn rights(&self, schema: &str, table: &str) -> Rights {
+        let table = self.table(schema, table);
+
+        Rights {
+            read: table.and_then(|t| t.read).unwrap_or(self.read),
+            create: table.and_then(|t| t.create).unwrap_or(self.create),
+            update: table.and_then(|t| t.update).unwrap_or(self.update),
+            delete: table.and_then(|t| t.delete).unwrap_or(self.delete),
+        }
+    }
+
+    pub fn hidden(&self, schema: &str, table: &str, column: &str) -> bool {
+        self.table(schema, table)
+            .and_then(|t| t.hidden.as_ref())
+            .is_some_and(|hidden| hidden.iter().any(|c| c == column))
+    }
+
+    /// Whether any of the table's columns are hidden or read-only
+    pub fn limits_columns(&self, schema: &str, table: &str) -> bool {
+        self.table(schema, table).is_some_and(|t| {
+            t.hidden.iter().chain(&t.readonly).any(|columns| !columns.is_empty())
+        })
+    }
+
+    pub fn readonly(&self, schema: &str, table: &str, column: &str) -> bool {
+        self.table(schema, table)
+            .and_then(|t| t.readonly.as_ref())
+            .is_some_and(|readonly| readonly.iter().any(|c| c == column))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SessionsConfig {
+    /// How long, in seconds, users stay logged in
+    pub max_age: u64,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self { max_age: 60 * 60 * 12 }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
     pub scope: ScopeConfig,
+    /// Functions to offer as actions, if they should be scoped differently
+    /// than tables
+    pub functions: Option<ScopeConfig>,
     pub tables: Option<Vec<TableConfig>>,
+    #[serde(default)]
+    pub uploads: UploadsConfig,
+    /// Everyone who can log in, where nobody can if there are none
+    pub users: Option<Vec<UserConfig>>,
+    /// Settings to pass the logged in user's attributes along in, keyed by setting
+    /// name, eg. `"app.user_id" = "id"`, for use in row-level security policies
+    /// and triggers by way of `current_setting('app.user_id')`
+    pub session_variables: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub sessions: SessionsConfig,
+    /// Permissions users can be given, by naming one of these
+    pub roles: Option<Vec<RoleConfig>>,
 }
 
 impl Config {
     pub fn load(filepath: &str) -> Config {
         let contents = fs::read_to_string(filepath).unwrap();
-        toml::from_str(&contents).unwrap()
+        let config: Config = toml::from_str(&contents).unwrap();
+
+        // Users with missing permissions would otherwise be let do anything
+        for user in config.users.iter().flatten() {
+            if let Some(permissions) = &user.permissions {
+                assert!(config.role(permissions).is_some(), "User {} has the permissions of role {permissions}, which isn't in `roles`", user.name);
+            }
+        }
+
+        config
+    }
+
+    pub fn function_scope(&self) -> &ScopeConfig {
+        self.functions.as_ref().unwrap_or(&self.scope)
+    }
+
+    /// The configured label for a column, or else its name
+    pub fn label<'a>(&'a self, schema: &str, table: &str, column: &'a str) -> &'a str {
+        self.table(schema, table)
+            .and_then(|t| t.label(column))
+            .unwrap_or(column)
+    }
+
+    pub fn user(&self, name: &str) -> Option<&UserConfig> {
+        self.users.as_ref()?.iter().find(|u| u.name == name)
+    }
+
+    pub fn role(&self, name: &str) -> Option<&RoleConfig> {
+        self.roles.as_ref()?.iter().find(|r| r.name == name)
+    }
+
+    /// Finds the configuration for a table, where tables configured without
+    /// a schema match tables of that name in any schema
+    pub fn table(&self, schema: &str, table: &str) -> Option<&TableConfig> {
+        self.tables.as_ref()?.iter().find(|t| {
+            t.table == table && t.schema.as_deref().is_none_or(|s| s == schema)
+        })
     }
 }
@@ -0,0 +1,64 @@
+//! Binary columns are never selected whole for display, only summarized by
+//! their size and first few bytes, which is enough to tell what they contain
+
+/// How many leading bytes are selected for sniffing the content type
+const HEAD_LENGTH: usize = 16;
+
+/// Selects a summary of the column as `<size> <leading bytes as hex>`
+pub fn summary_expression(column: &str) -> String {
+    format!(
+        r#"octet_length("{column}") || ' ' || encode(substring("{column}" from 1 for {HEAD_LENGTH}), 'hex')"#
+    )
+}
+
+pub struct Summary {
+    pub size: i64,
+    pub content_type: Option<&'static str>,
+}
+
+impl Summary {
+    /// Parses a summary selected with `summary_expression`
+    pub fn parse(text: &str) -> Option<Self> {
+        let (size, head) = text.split_once(' ')?;
+
+        let head = (0..head.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(head.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            size: size.parse().ok()?,
+            content_type: sniff(&head),
+        })
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.content_type.is_some_and(|c| c.starts_with("image/"))
+    }
+}
+
+/// Guesses the content type from the leading "magic" bytes
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, ..] => Some("image/png"),
+        [0xff, 0xd8, 0xff, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [b'%', b'P', b'D', b'F', b'-', ..] => Some("application/pdf"),
+        [b'P', b'K', 0x03, 0x04, ..] => Some("application/zip"),
+        _ => None,
+    }
+}
+
+/// A file extension for downloads of the given content type
+pub fn extension(content_type: Option<&str>) -> &'static str {
+    match content_type {
+        Some("image/png") => "png",
+        Some("image/jpeg") => "jpg",
+        Some("image/gif") => "gif",
+        Some("image/webp") => "webp",
+        Some("application/pdf") => "pdf",
+        Some("application/zip") => "zip",
+        _ => "bin",
+    }
+}
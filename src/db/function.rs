@@ -0,0 +1,63 @@
+use crate::{db::{Column, Table}, Config};
+use serde::Deserialize;
+use sqlx::{postgres::{types::Oid, PgConnection}, types::Json};
+
+/// A function that can be called as an "action", with its arguments and
+/// results described as columns
+#[derive(Clone, Debug, Deserialize)]
+pub struct Function {
+    pub oid: Oid,
+    pub name: String,
+    pub schema: String,
+    pub description: Option<String>,
+    pub returns_set: bool,
+    /// One of `immutable`, `stable` or `volatile`
+    pub volatility: String,
+    pub arguments: Vec<Column>,
+    pub results: Vec<Column>,
+}
+
+impl Function {
+    pub async fn load(conn: &mut PgConnection, config: &Config, oid: u32) -> Option<Function> {
+        let scope = config.function_scope();
+
+        let mut function = sqlx::query_file_as!(
+            Function,
+            "queries/function-details.sql",
+            &scope.include,
+            &scope.exclude,
+            Oid(oid)
+        )
+            .fetch_optional(&mut *conn)
+            .await
+            .unwrap()?;
+
+        for argument in &mut function.arguments {
+            if let Some(Oid(type_oid)) = argument.composite {
+                let composite = Table::load_relation(&mut *conn, config, type_oid, true).await;
+
+                argument.attributes = composite.map(|c| c.columns).unwrap_or_default();
+            }
+        }
+
+        Some(function)
+    }
+
+    /// The results as a table of their own, so they can be selected & shown
+    /// like records, but never edited
+    pub fn results_table(&self) -> Table {
+        Table {
+            columns: self.results.clone(),
+            kind: "function result".to_owned(),
+            name: self.name.clone(),
+            oid: self.oid,
+            schema: self.schema.clone(),
+            description: None,
+            insertable: false,
+            updatable: false,
+            deletable: false,
+            unique_keys: Json(Vec::new()),
+            referenced_by: Json(Vec::new()),
+        }
+    }
+}
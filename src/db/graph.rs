@@ -0,0 +1,497 @@
+//! A record along with the records related to it by foreign keys, as a document
+//! that can be imported into another database. From the record, records
+//! referencing it (eg. a band's members) are followed down to a given depth, and
+//! from all of those, the records they reference (eg. each member's musician),
+//! so the graph is complete enough to be inserted elsewhere. Records referenced
+//! by others aren't followed back down to everything else referencing them.
+//!
+//! Records are listed parents first, so they can be inserted in order, where
+//! generated `id`s are left to the database and references to them remapped.
+//! Referenced records often exist already (eg. the same musician), in which
+//! case the existing record is referenced instead.
+
+use crate::{config::RoleConfig, db::{Column, Table}, Config};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::{types::Oid, PgRow}, Connection, PgConnection, Postgres, Row, Transaction};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Graph {
+    /// The record the graph was exported for
+    pub root: Key,
+    pub records: Vec<Record>,
+}
+
+/// Identifies a record, by its table and `id`
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Key {
+    pub schema: String,
+    pub table: String,
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Record {
+    #[serde(flatten)]
+    pub key: Key,
+    /// Every column's value as text, like everything else is exchanged with the
+    /// database, where `None` is null
+    pub values: BTreeMap<String, Option<String>>,
+    /// Whether the record is only included because others reference it, so
+    /// that an existing record can be used instead
+    #[serde(default)]
+    pub referenced: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The document can't be imported, eg. it names a table that isn't available
+    Invalid(String),
+    Sql(sqlx::Error),
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        Error::Sql(error)
+    }
+}
+
+/// Whether a record was reached by following references down from the root, in
+/// which case records referencing it are followed too
+#[derive(Clone, Copy, PartialEq)]
+enum Reach {
+    Owned,
+    Referenced,
+}
+
+/// Tables loaded along the way, by their OID
+struct Tables<'a> {
+    config: &'a Config,
+    role: Option<&'a RoleConfig>,
+    loaded: HashMap<u32, Option<Table>>,
+}
+
+impl<'a> Tables<'a> {
+    fn new(config: &'a Config, role: Option<&'a RoleConfig>) -> Self {
+        Self { config, role, loaded: HashMap::new() }
+    }
+
+    /// Tables out of scope (or that the role can't read) aren't available, so
+    /// records in them are left out, as are those without an `id` to key them by
+    async fn get(&mut self, conn: &mut PgConnection, oid: u32) -> Option<&Table> {
+        if !self.loaded.contains_key(&oid) {
+            // TODO: Don't rely on `id` field
+            let table = Table::load(conn, self.config, self.role, oid).await
+                .filter(|t| t.columns.iter().any(|c| c.name == "id"));
+            self.loaded.insert(oid, table);
+        }
+
+        self.loaded.get(&oid).and_then(|t| t.as_ref())
+    }
+}
+
+/// Collects a record and the records related to it, following records that
+/// reference it at most `depth` records away from it, and always the records
+/// any of them reference, so that none are missing on import
+pub async fn export(
+    conn: &mut PgConnection,
+    config: &Config,
+    role: Option<&RoleConfig>,
+    table: &Table,
+    record_id: &str,
+    depth: usize,
+) -> Result<Option<Graph>, sqlx::Error> {
+    let mut tables = Tables::new(config, role);
+    let mut records = Vec::new();
+    let mut seen = HashSet::new();
+
+    let root = match select(conn, table, "id", record_id).await?.pop() {
+        Some(row) => to_record(table, &row)?,
+        None => return Ok(None),
+    };
+
+    let root_key = root.key.clone();
+    let mut queue = VecDeque::from([(table.oid.0, root, Reach::Owned, 0)]);
+
+    seen.insert(root_key.clone());
+
+    // Breadth first, so records are followed from wherever they're closest
+    while let Some((oid, record, reach, distance)) = queue.pop_front() {
+        let table = tables.get(conn, oid).await.unwrap().clone();
+
+        // Records this one references, eg. a member's musician, however far away
+        for column in &table.columns {
+            let (reference, value) = match (&column.references, record.values.get(&column.name)) {
+                (Some(reference), Some(Some(value))) => (reference, value),
+                _ => continue,
+            };
+
+            if let Some(referenced) = tables.get(conn, reference.table.0).await {
+                for row in select(conn, referenced, &reference.column, value).await? {
+                    let mut related = to_record(referenced, &row)?;
+                    related.referenced = true;
+
+                    if seen.insert(related.key.clone()) {
+                        queue.push_back((reference.table.0, related, Reach::Referenced, distance + 1));
+                    }
+                }
+            }
+        }
+
+        // Records referencing this one, eg. a band's members
+        if reach == Reach::Owned && distance < depth {
+            for referrer in table.referenced_by.iter() {
+                let value = match record.values.get(&referrer.references) {
+                    Some(Some(value)) => value,
+                    _ => continue,
+                };
+
+                if let Some(referencing) = tables.get(conn, referrer.table.0).await {
+                    for row in select(conn, referencing, &referrer.column, value).await? {
+                        let related = to_record(referencing, &row)?;
+
+                        if seen.insert(related.key.clone()) {
+                            queue.push_back((referrer.table.0, related, Reach::Owned, distance + 1));
+                        }
+                    }
+                }
+            }
+        }
+
+        records.push((oid, record));
+    }
+
+    let records = in_dependency_order(conn, &mut tables, records).await;
+
+    Ok(Some(Graph { root: root_key, records }))
+}
+
+/// Orders records so that each comes after any records it references, as far as
+/// references allow; records in a cycle are left in whatever order they're in
+async fn in_dependency_order(
+    conn: &mut PgConnection,
+    tables: &mut Tables<'_>,
+    records: Vec<(u32, Record)>,
+) -> Vec<Record> {
+    let mut pending = Vec::new();
+
+    for (oid, record) in records {
+        let table = tables.get(conn, oid).await.unwrap().clone();
+        let mut parents = Vec::new();
+
+        for column in &table.columns {
+            if let (Some(reference), Some(Some(value))) = (&column.references, record.values.get(&column.name)) {
+                if reference.column == "id" {
+                    let referenced = tables.get(conn, reference.table.0).await;
+
+                    if let Some(referenced) = referenced {
+                        parents.push(Key {
+                            schema: referenced.schema.clone(),
+                            table: referenced.name.clone(),
+                            id: value.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        // A record referencing itself doesn't have to wait for itself
+        parents.retain(|p| p != &record.key);
+        pending.push((record, parents));
+    }
+
+    let keys = pending.iter().map(|(r, _)| r.key.clone()).collect::<HashSet<_>>();
+    let mut done = HashSet::new();
+    let mut ordered = Vec::new();
+
+    while !pending.is_empty() {
+        let ready = pending.iter()
+            .position(|(_, parents)| parents.iter().all(|p| done.contains(p) || !keys.contains(p)))
+            .unwrap_or(0);
+
+        let (record, _) = pending.remove(ready);
+
+        done.insert(record.key.clone());
+        ordered.push(record);
+    }
+
+    ordered
+}
+
+/// The outcome of importing a graph
+#[derive(Debug)]
+pub struct Imported {
+    /// The new `id` of the record the graph was exported for
+    pub root_id: String,
+    pub inserted: usize,
+    /// Referenced records that already existed, which were used instead
+    pub existing: usize,
+}
+
+/// Inserts a graph's records in order within a single transaction, where
+/// records only included because others reference them are matched to existing
+/// records by their unique constraints first
+pub async fn import(
+    conn: &mut PgConnection,
+    config: &Config,
+    role: Option<&RoleConfig>,
+    graph: &Graph,
+) -> Result<Imported, Error> {
+    let mut transaction = conn.begin().await?;
+    let mut tables: HashMap<(String, String), Table> = HashMap::new();
+    // New ids of inserted records by their table's OID & their old id
+    let mut ids: HashMap<(u32, String), String> = HashMap::new();
+    let mut existing = 0;
+
+    for record in &graph.records {
+        let name = (record.key.schema.clone(), record.key.table.clone());
+
+        if !tables.contains_key(&name) {
+            let table = find(&mut transaction, config, role, &name.0, &name.1).await?
+                .ok_or_else(|| Error::Invalid(format!("Table {}.{} isn't available", name.0, name.1)))?;
+
+            tables.insert(name.clone(), table);
+        }
+
+        let table = &tables[&name];
+        let values = values(table, record, &ids)?;
+
+        let found = match record.referenced {
+            true => find_existing(&mut transaction, table, &values).await?,
+            false => None,
+        };
+
+        let new_id = match found {
+            Some(id) => {
+                existing += 1;
+                id
+            }
+            None if !table.insertable => return Err(Error::Invalid(format!(
+                "{}.{} record {}: Records can't be inserted into the table",
+                record.key.schema,
+                record.key.table,
+                record.key.id,
+            ))),
+            None => insert(&mut transaction, table, &values).await.map_err(|e| match e {
+                sqlx::Error::Database(e) => Error::Invalid(format!(
+                    "{}.{} record {}: {}",
+                    record.key.schema,
+                    record.key.table,
+                    record.key.id,
+                    e.message(),
+                )),
+                e => Error::Sql(e),
+            })?,
+        };
+
+        ids.insert((table.oid.0, record.key.id.clone()), new_id);
+    }
+
+    transaction.commit().await?;
+
+    let root_id = tables.get(&(graph.root.schema.clone(), graph.root.table.clone()))
+        .and_then(|table| ids.get(&(table.oid.0, graph.root.id.clone())))
+        .ok_or_else(|| Error::Invalid("The root record isn't in the document".to_owned()))?;
+
+    Ok(Imported {
+        root_id: root_id.clone(),
+        inserted: graph.records.len() - existing,
+        existing,
+    })
+}
+
+/// The values to insert for a record, leaving its `id` to the database if it can
+/// be generated, and pointing its references at the records inserted (or found)
+/// in place of those they referenced. References to records that weren't
+/// inserted before (eg. left out of the document) are rejected, rather than
+/// pointing at whatever has their old `id` here.
+fn values<'a>(
+    table: &'a Table,
+    record: &'a Record,
+    ids: &'a HashMap<(u32, String), String>,
+) -> Result<Vec<(&'a Column, Option<&'a String>)>, Error> {
+    table.columns.iter()
+        // TODO: Don't rely on `id` field
+        .filter(|c| c.generated.is_none() && !(c.name == "id" && (c.identity.is_some() || c.has_default())))
+        .filter_map(|column| {
+            let value = record.values.get(&column.name)?.as_ref();
+
+            let value = match (&column.references, value) {
+                (Some(reference), Some(value)) if reference.column == "id" => {
+                    match ids.get(&(reference.table.0, value.clone())) {
+                        Some(id) => Some(id),
+                        None => return Some(Err(Error::Invalid(format!(
+                            "{}.{} record {}: {} references a record that isn't in the document",
+                            record.key.schema,
+                            record.key.table,
+                            record.key.id,
+                            column.name,
+                        )))),
+                    }
+                }
+                (_, value) => value,
+            };
+
+            Some(Ok((column, value)))
+        })
+        .collect()
+}
+
+/// The `id` of a record with the same values for any of the table's unique
+/// constraints, ignoring constraints on columns left to the database
+async fn find_existing(
+    transaction: &mut Transaction<'_, Postgres>,
+    table: &Table,
+    values: &[(&Column, Option<&String>)],
+) -> Result<Option<String>, sqlx::Error> {
+    for key in table.unique_keys.iter() {
+        let key_values = key.columns.iter()
+            .map(|name| values.iter().find(|(c, _)| &c.name == name).and_then(|(c, v)| Some((*c, (*v)?))))
+            .collect::<Option<Vec<_>>>();
+
+        // Nulls are never equal, so keys with null values never match either
+        let key_values = match key_values {
+            Some(key_values) => key_values,
+            None => continue,
+        };
+
+        let conditions = key_values.iter().enumerate()
+            .map(|(i, (column, _))| format!("\"{}\"::text = (${}::{})::text", column.name, i + 1, column.cast_type()))
+            .collect::<Vec<_>>();
+
+        // TODO: Don't rely on `id` field
+        let statement = format!(r#"
+            SELECT id::text FROM "{}"."{}"
+            WHERE {}
+            LIMIT 1
+            "#,
+            table.schema,
+            table.name,
+            conditions.join(" AND "),
+        );
+
+        let mut query = sqlx::query_scalar(&statement);
+
+        for (_, value) in key_values {
+            query = query.bind(value);
+        }
+
+        if let Some(id) = query.fetch_optional(&mut *transaction).await? {
+            return Ok(Some(id));
+        }
+    }
+
+    Ok(None)
+}
+
+async fn insert(
+    transaction: &mut Transaction<'_, Postgres>,
+    table: &Table,
+    values: &[(&Column, Option<&String>)],
+) -> Result<String, sqlx::Error> {
+    let mut columns = Vec::new();
+    let mut placeholders = Vec::new();
+    let mut bind_params = Vec::new();
+
+    // Columns the role can't insert into are left to their default, as
+    // they're still needed to find existing records
+    for (column, value) in values.iter().filter(|(c, _)| c.insertable) {
+        columns.push(format!("\"{}\"", column.name));
+
+        match value {
+            Some(value) => {
+                bind_params.push(value);
+                placeholders.push(format!("${}::{}", bind_params.len(), column.cast_type()));
+            }
+            None => placeholders.push("NULL".to_owned()),
+        }
+    }
+
+    let statement = match columns.is_empty() {
+        true => format!(r#"
+            INSERT INTO "{}"."{}" DEFAULT VALUES
+            RETURNING id::text
+            "#,
+            table.schema,
+            table.name,
+        ),
+        false => format!(r#"
+            INSERT INTO "{}"."{}" ({})
+                VALUES ({})
+            RETURNING id::text
+            "#,
+            table.schema,
+            table.name,
+            columns.join(", "),
+            placeholders.join(", "),
+        ),
+    };
+
+    let mut query = sqlx::query_scalar(&statement);
+
+    for param in bind_params {
+        query = query.bind(param);
+    }
+
+    query.fetch_one(&mut *transaction).await
+}
+
+async fn find(
+    conn: &mut PgConnection,
+    config: &Config,
+    role: Option<&RoleConfig>,
+    schema: &str,
+    name: &str,
+) -> Result<Option<Table>, sqlx::Error> {
+    let oid: Option<Oid> = sqlx::query_scalar("SELECT to_regclass(format('%I.%I', $1::text, $2::text))::oid")
+        .bind(schema)
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(match oid {
+        Some(oid) => Table::load(conn, config, role, oid.0).await,
+        None => None,
+    })
+}
+
+/// Selects the records of a table whose column has the given value, with every
+/// column as text
+async fn select(conn: &mut PgConnection, table: &Table, column: &str, value: &str) -> Result<Vec<PgRow>, sqlx::Error> {
+    let columns = table.columns.iter()
+        .map(|c| format!("\"{0}\"::text AS \"{0}\"", c.name))
+        .collect::<Vec<_>>();
+
+    // TODO: Don't rely on `id` field
+    let statement = format!(r#"
+        SELECT {} FROM "{}"."{}"
+        WHERE "{}"::text = $1
+        ORDER BY id
+        "#,
+        columns.join(", "),
+        table.schema,
+        table.name,
+        column,
+    );
+
+    sqlx::query(&statement)
+        .bind(value)
+        .fetch_all(conn)
+        .await
+}
+
+fn to_record(table: &Table, row: &PgRow) -> Result<Record, sqlx::Error> {
+    let values = table.columns.iter()
+        .map(|c: &Column| Ok((c.name.clone(), row.try_get::<Option<String>, _>(c.name.as_str())?)))
+        .collect::<Result<BTreeMap<_, _>, sqlx::Error>>()?;
+
+    // TODO: Don't rely on `id` field
+    let id = values.get("id").cloned().flatten()
+        .ok_or_else(|| sqlx::Error::ColumnNotFound("id".to_owned()))?;
+
+    Ok(Record {
+        key: Key { schema: table.schema.clone(), table: table.name.clone(), id },
+        values,
+        referenced: false,
+    })
+}
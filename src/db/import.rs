@@ -0,0 +1,236 @@
+//! Importing CSV files into a table, where each of the file's fields is mapped
+//! onto a column (or ignored). Every row is inserted within a single transaction,
+//! each behind a savepoint so that one failing row doesn't hide the rest from the
+//! report; if any row fails, or when only trying the import out, nothing is kept.
+
+use crate::db::{Column, Table, UniqueKey};
+use sqlx::{Connection, PgConnection};
+
+/// A CSV file to import, along with how it maps onto the table
+#[derive(Debug)]
+pub struct Import {
+    /// The file's contents, which are kept with the session from one step to
+    /// the next
+    pub data: String,
+    pub delimiter: u8,
+    pub headers: Vec<String>,
+    /// The column each field is imported into, by position, if any
+    pub columns: Vec<Option<String>>,
+    /// The unique constraint by which rows update existing records rather than
+    /// being inserted, if any
+    pub key: Option<String>,
+}
+
+/// A row that failed to import, by its line in the file
+#[derive(Debug)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+    /// The constraint the row violated, if that's why it failed
+    pub constraint: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Outcome {
+    /// Records inserted or updated, even if they were then rolled back
+    pub imported: u64,
+    pub errors: Vec<RowError>,
+    pub committed: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The mapping itself can't work, eg. a column is mapped twice
+    Invalid(String),
+    Sql(sqlx::Error),
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        Error::Sql(error)
+    }
+}
+
+impl Import {
+    /// Reads the header row of a file, without mapping any of its fields yet
+    pub fn parse(data: String, delimiter: u8) -> Result<Self, csv::Error> {
+        // Spreadsheets tend to save CSV files with a byte order mark
+        let data = match data.strip_prefix('\u{feff}') {
+            Some(data) => data.to_owned(),
+            None => data,
+        };
+
+        let headers = reader(&data, delimiter).headers()?
+            .iter()
+            .map(|h| h.to_owned())
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            columns: vec![None; headers.len()],
+            data,
+            delimiter,
+            headers,
+            key: None,
+        })
+    }
+
+    /// Maps fields onto the columns they're named after, comparing names case
+    /// insensitively to both column names and their labels (eg. from an export)
+    pub fn auto_map<'a>(mut self, columns: impl Iterator<Item = (&'a str, &'a str)> + Clone) -> Self {
+        for (header, column) in self.headers.iter().zip(self.columns.iter_mut()) {
+            let header = header.trim();
+
+            *column = columns.clone()
+                .find(|(name, label)| name.eq_ignore_ascii_case(header) || label.eq_ignore_ascii_case(header))
+                .map(|(name, _)| name.to_owned());
+        }
+
+        self
+    }
+
+    /// Inserts (or upserts) every row, only committing them if they all succeed
+    /// and `commit` is set, which otherwise makes this a dry run
+    pub async fn run(&self, conn: &mut PgConnection, table: &Table, commit: bool) -> Result<Outcome, Error> {
+        let statement = self.statement(table)?;
+        let mut outcome = Outcome::default();
+        let mut transaction = conn.begin().await?;
+
+        for record in reader(&self.data, self.delimiter).records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    outcome.errors.push(RowError {
+                        line: e.position().map_or(0, |p| p.line()),
+                        message: e.to_string(),
+                        constraint: None,
+                    });
+                    continue;
+                }
+            };
+
+            let mut query = sqlx::query(&statement);
+
+            // Empty fields are null, as Postgres itself writes them in CSV
+            for (i, _) in self.columns.iter().enumerate().filter(|(_, c)| c.is_some()) {
+                query = query.bind(record.get(i).filter(|f| !f.is_empty()));
+            }
+
+            let mut savepoint = transaction.begin().await?;
+
+            match query.execute(&mut savepoint).await {
+                Ok(result) => {
+                    savepoint.commit().await?;
+                    outcome.imported += result.rows_affected();
+                }
+                Err(sqlx::Error::Database(e)) => {
+                    savepoint.rollback().await?;
+                    outcome.errors.push(RowError {
+                        line: record.position().map_or(0, |p| p.line()),
+                        message: e.message().to_owned(),
+                        constraint: e.constraint().map(|c| c.to_owned()),
+                    });
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if commit && outcome.errors.is_empty() {
+            transaction.commit().await?;
+            outcome.committed = true;
+        } else {
+            transaction.rollback().await?;
+        }
+
+        Ok(outcome)
+    }
+
+    fn statement(&self, table: &Table) -> Result<String, Error> {
+        let mut columns = Vec::new();
+
+        for name in self.columns.iter().flatten() {
+            let column = table.columns.iter()
+                .find(|c| &c.name == name && c.generated.is_none() && c.insertable)
+                .ok_or_else(|| Error::Invalid(format!("There's no column {name} to import into")))?;
+
+            if columns.iter().any(|c: &&Column| &c.name == name) {
+                return Err(Error::Invalid(format!("Column {name} is mapped to more than one field")));
+            }
+
+            columns.push(column);
+        }
+
+        if columns.is_empty() {
+            return Err(Error::Invalid("No fields are mapped to columns".to_owned()));
+        }
+
+        let names = columns.iter()
+            .map(|c| format!("\"{}\"", c.name))
+            .collect::<Vec<_>>();
+        let placeholders = columns.iter().enumerate()
+            .map(|(i, c)| format!("${}::{}", i + 1, c.cast_type()))
+            .collect::<Vec<_>>();
+
+        // Identities are normally left to the database, but updating records by
+        // their primary key means giving it (where new records given an identity
+        // beyond its sequence may then clash with records added later on)
+        let overriding = match columns.iter().any(|c| c.identity.as_deref() == Some("always")) {
+            true => "OVERRIDING SYSTEM VALUE",
+            false => "",
+        };
+
+        let conflict = match &self.key {
+            Some(key) if !table.updatable => {
+                return Err(Error::Invalid(format!("Records can't be updated by {key}, as the table's records can't be updated")));
+            }
+            Some(key) => {
+                let key = table.unique_keys.iter()
+                    .find(|k| &k.name == key)
+                    .ok_or_else(|| Error::Invalid(format!("There's no unique constraint {key}")))?;
+
+                on_conflict(key, &columns)?
+            }
+            None => String::new(),
+        };
+
+        Ok(format!(r#"
+            INSERT INTO "{}"."{}" ({})
+                {}
+                VALUES ({})
+                {}
+            "#,
+            table.schema,
+            table.name,
+            names.join(", "),
+            overriding,
+            placeholders.join(", "),
+            conflict,
+        ))
+    }
+}
+
+/// Updates the existing record instead, with every mapped column that isn't
+/// part of the key itself, and that can be updated
+fn on_conflict(key: &UniqueKey, columns: &[&Column]) -> Result<String, Error> {
+    if let Some(missing) = key.columns.iter().find(|k| !columns.iter().any(|c| &&c.name == k)) {
+        return Err(Error::Invalid(format!("Column {missing} must be mapped to update records by {}", key.name)));
+    }
+
+    let assignments = columns.iter()
+        .filter(|c| !key.columns.contains(&c.name) && c.updatable)
+        .map(|c| format!("\"{0}\" = EXCLUDED.\"{0}\"", c.name))
+        .collect::<Vec<_>>();
+
+    Ok(match assignments.is_empty() {
+        true => format!("ON CONFLICT ON CONSTRAINT \"{}\" DO NOTHING", key.name),
+        false => format!("ON CONFLICT ON CONSTRAINT \"{}\" DO UPDATE SET {}", key.name, assignments.join(", ")),
+    })
+}
+
+/// Rows may have fewer or more fields than the header, where missing fields
+/// are null and extra fields are ignored
+fn reader(data: &str, delimiter: u8) -> csv::Reader<&[u8]> {
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(data.as_bytes())
+}
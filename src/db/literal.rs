@@ -0,0 +1,424 @@
+//! Encoding & decoding of the text representations Postgres uses for
+//! non-scalar values, since every value is exchanged with the database as text
+
+/// Quotes a single element for use inside an array literal, where quotes
+/// and backslashes need escaping
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+
+    format!("\"{escaped}\"")
+}
+
+/// Encodes elements as a one-dimensional array literal, eg. `{"a",NULL}`, where
+/// `None` elements are null
+pub fn encode_array<S: AsRef<str>>(elements: &[Option<S>]) -> String {
+    let elements = elements.iter()
+        .map(|e| e.as_ref().map_or("NULL".to_owned(), |e| quote(e.as_ref())))
+        .collect::<Vec<_>>();
+
+    format!("{{{}}}", elements.join(","))
+}
+
+/// Decodes a one-dimensional array literal into its elements, with `None` for
+/// any `NULL` elements. Multi-dimensional arrays and arrays with explicit bounds
+/// (eg. `[0:1]={a,b}`) are not supported.
+pub fn decode_array(literal: &str) -> Option<Vec<Option<String>>> {
+    let inner = literal.strip_prefix('{')?.strip_suffix('}')?;
+    let mut elements = Vec::new();
+
+    if inner.is_empty() {
+        return Some(elements);
+    }
+
+    let mut chars = inner.chars().peekable();
+
+    loop {
+        let mut element = String::new();
+        let mut quoted = false;
+
+        match chars.peek() {
+            Some('"') => {
+                quoted = true;
+                chars.next();
+
+                loop {
+                    match chars.next()? {
+                        '\\' => element.push(chars.next()?),
+                        '"' => break,
+                        c => element.push(c),
+                    }
+                }
+            }
+            Some('{') => return None,
+            _ => {
+                while let Some(c) = chars.next_if(|c| *c != ',') {
+                    match c {
+                        '\\' => element.push(chars.next()?),
+                        '{' | '}' | '"' => return None,
+                        c => element.push(c),
+                    }
+                }
+            }
+        }
+
+        let is_null = !quoted && element.eq_ignore_ascii_case("null");
+        elements.push(if is_null { None } else { Some(element) });
+
+        match chars.next() {
+            Some(',') => continue,
+            None => break,
+            Some(_) => return None,
+        }
+    }
+
+    Some(elements)
+}
+
+/// The bounds of a non-empty range, where missing bounds are unbounded
+#[derive(Debug, Default, PartialEq)]
+pub struct Range {
+    pub lower: Option<String>,
+    pub upper: Option<String>,
+    pub lower_inclusive: bool,
+    pub upper_inclusive: bool,
+}
+
+/// Encodes bounds as a range literal, eg. `["2022-01-01","2022-02-01")`
+pub fn encode_range(range: &Range) -> String {
+    let lower = range.lower.as_deref().map(quote).unwrap_or_default();
+    let upper = range.upper.as_deref().map(quote).unwrap_or_default();
+
+    format!(
+        "{}{lower},{upper}{}",
+        if range.lower_inclusive { '[' } else { '(' },
+        if range.upper_inclusive { ']' } else { ')' },
+    )
+}
+
+/// Decodes a range literal into its bounds; empty ranges decode to `None`
+/// since they don't have any
+pub fn decode_range(literal: &str) -> Option<Range> {
+    let lower_inclusive = match literal.chars().next()? {
+        '[' => true,
+        '(' => false,
+        _ => return None,
+    };
+    let upper_inclusive = match literal.chars().last()? {
+        ']' => true,
+        ')' => false,
+        _ => return None,
+    };
+
+    let [lower, upper]: [Option<String>; 2] = split_fields(&literal[1..literal.len() - 1])?
+        .try_into()
+        .ok()?;
+
+    Some(Range { lower, upper, lower_inclusive, upper_inclusive })
+}
+
+/// Encodes the fields of a composite value as a row literal, eg. `("a",,"c")`,
+/// where `None` fields are null
+pub fn encode_row(fields: &[Option<&str>]) -> String {
+    let fields = fields.iter()
+        .map(|f| f.map(quote).unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    format!("({})", fields.join(","))
+}
+
+/// Decodes a row literal into its fields, with `None` for null fields
+pub fn decode_row(literal: &str) -> Option<Vec<Option<String>>> {
+    let inner = literal.strip_prefix('(')?.strip_suffix(')')?;
+
+    split_fields(inner)
+}
+
+/// Splits the comma-separated fields of a range or row literal, where fields
+/// can be quoted (with quotes doubled or escaped within them) and missing
+/// fields are unbounded or null respectively
+fn split_fields(inner: &str) -> Option<Vec<Option<String>>> {
+    let mut chars = inner.chars().peekable();
+    let mut fields = Vec::new();
+
+    loop {
+        let mut value = String::new();
+        let mut quoted = false;
+        let mut in_quotes = false;
+
+        while chars.peek().is_some_and(|c| *c != ',' || in_quotes) {
+            match chars.next()? {
+                '"' if in_quotes && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    value.push('"');
+                }
+                '"' => {
+                    quoted = true;
+                    in_quotes = !in_quotes;
+                }
+                '\\' => value.push(chars.next()?),
+                c => value.push(c),
+            }
+        }
+
+        // Only a field that's missing altogether is null, not a quoted empty string
+        fields.push(if quoted || !value.is_empty() { Some(value) } else { None });
+
+        match chars.next() {
+            Some(',') => continue,
+            _ => break,
+        }
+    }
+
+    Some(fields)
+}
+
+/// Quotes a value as an escaped string constant, eg. `E'it''s'`, for statements
+/// that can't take bind params (eg. `COPY`)
+pub fn quote_constant(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('\'', "''");
+
+    format!("E'{escaped}'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(elements: &[Option<&str>]) -> Vec<Option<String>> {
+        elements.iter().map(|e| e.map(str::to_owned)).collect()
+    }
+
+    #[test]
+    fn array_round_trip() {
+        let elements = [Some("plain"), Some("with space"), Some("a,b"), Some("\"quoted\""), Some("back\\slash"), Some("\\\""), Some(""), Some("{a,b}"), Some("NULL"), Some("null"), None];
+        let decoded = decode_array(&encode_array(&elements)).unwrap();
+
+        assert_eq!(decoded, strings(&elements));
+    }
+
+    #[test]
+    fn array_empty() {
+        assert_eq!(encode_array::<&str>(&[]), "{}");
+        assert_eq!(encode_array(&[Some("")]), r#"{""}"#);
+        assert_eq!(decode_array("{}"), Some(Vec::new()));
+    }
+
+    #[test]
+    fn array_quoting() {
+        assert_eq!(encode_array(&[Some("a\"b"), Some("c\\d")]), r#"{"a\"b","c\\d"}"#);
+        assert_eq!(decode_array(r#"{"a\"b","c\\d"}"#), Some(strings(&[Some("a\"b"), Some("c\\d")])));
+    }
+
+    #[test]
+    fn array_nulls() {
+        // Only unquoted `NULL`s are null, in any case
+        assert_eq!(
+            decode_array(r#"{NULL,null,"NULL",a}"#),
+            Some(strings(&[None, None, Some("NULL"), Some("a")])),
+        );
+        assert_eq!(encode_array(&[Some("NULL"), None]), r#"{"NULL",NULL}"#);
+    }
+
+    #[test]
+    fn array_as_output_by_postgres() {
+        assert_eq!(
+            decode_array(r#"{1,"two words","a,b",\\,""}"#),
+            Some(strings(&[Some("1"), Some("two words"), Some("a,b"), Some("\\"), Some("")])),
+        );
+    }
+
+    #[test]
+    fn array_nested_unsupported() {
+        assert_eq!(decode_array("{{1,2},{3,4}}"), None);
+        assert_eq!(decode_array("{1,{2}}"), None);
+        assert_eq!(decode_array("[0:1]={a,b}"), None);
+    }
+
+    #[test]
+    fn array_malformed() {
+        assert_eq!(decode_array("a,b"), None);
+        assert_eq!(decode_array("{\"a}"), None);
+        assert_eq!(decode_array("{\"a\"b}"), None);
+        assert_eq!(decode_array("{a\"b}"), None);
+    }
+
+    fn range(lower: Option<&str>, upper: Option<&str>, lower_inclusive: bool, upper_inclusive: bool) -> Range {
+        Range {
+            lower: lower.map(str::to_owned),
+            upper: upper.map(str::to_owned),
+            lower_inclusive,
+            upper_inclusive,
+        }
+    }
+
+    #[test]
+    fn range_round_trip() {
+        let ranges = [
+            range(Some("1"), Some("5"), true, false),
+            range(Some("1.5"), Some("2.5"), true, true),
+            range(Some("2022-01-01"), Some("2022-02-01"), false, false),
+            range(None, Some("5"), false, true),
+            range(Some("1"), None, true, false),
+            range(None, None, false, false),
+            range(Some("a,b"), Some("c\"d"), true, false),
+            range(Some("("), Some("]"), false, true),
+            range(Some("back\\slash"), Some(""), true, false),
+        ];
+
+        for range in ranges {
+            assert_eq!(decode_range(&encode_range(&range)), Some(range));
+        }
+    }
+
+    #[test]
+    fn range_bounds() {
+        assert_eq!(encode_range(&range(Some("1"), Some("5"), true, false)), r#"["1","5")"#);
+        assert_eq!(encode_range(&range(None, None, false, false)), "(,)");
+        assert_eq!(encode_range(&range(Some("a,b"), None, true, false)), r#"["a,b",)"#);
+    }
+
+    #[test]
+    fn range_as_output_by_postgres() {
+        assert_eq!(decode_range("[1,5)"), Some(range(Some("1"), Some("5"), true, false)));
+        assert_eq!(decode_range("[1.5,2.5]"), Some(range(Some("1.5"), Some("2.5"), true, true)));
+        assert_eq!(decode_range("(,5]"), Some(range(None, Some("5"), false, true)));
+        assert_eq!(decode_range("(,)"), Some(range(None, None, false, false)));
+        // Quotes are doubled within quoted bounds, and an empty bound is quoted
+        assert_eq!(decode_range(r#"["a,b","c""d")"#), Some(range(Some("a,b"), Some("c\"d"), true, false)));
+        assert_eq!(decode_range(r#"(,"x y")"#), Some(range(None, Some("x y"), false, false)));
+        assert_eq!(decode_range(r#"["",z)"#), Some(range(Some(""), Some("z"), true, false)));
+    }
+
+    #[test]
+    fn range_empty() {
+        assert_eq!(decode_range("empty"), None);
+    }
+
+    #[test]
+    fn range_malformed() {
+        assert_eq!(decode_range(""), None);
+        assert_eq!(decode_range("1,5"), None);
+        assert_eq!(decode_range("[1,5"), None);
+        assert_eq!(decode_range("[1,5,9)"), None);
+        assert_eq!(decode_range("[1)"), None);
+    }
+
+    #[test]
+    fn row_round_trip() {
+        let fields = [Some("plain"), None, Some(""), Some("a,b"), Some("\"quoted\""), Some("back\\slash"), Some("(x,y)"), Some("NULL")];
+        let decoded = decode_row(&encode_row(&fields)).unwrap();
+
+        assert_eq!(decoded, strings(&fields));
+    }
+
+    #[test]
+    fn row_null_fields() {
+        assert_eq!(encode_row(&[None, Some("a"), None]), r#"(,"a",)"#);
+        assert_eq!(decode_row("(,a,)"), Some(strings(&[None, Some("a"), None])));
+        // An empty string is quoted, unlike a null
+        assert_eq!(decode_row(r#"("",)"#), Some(strings(&[Some(""), None])));
+        assert_eq!(decode_row("()"), Some(strings(&[None])));
+    }
+
+    #[test]
+    fn row_nested() {
+        let inner = encode_row(&[Some("2"), Some("x,\"y\"")]);
+        let outer = encode_row(&[Some("1"), Some(&inner), None]);
+
+        let fields = decode_row(&outer).unwrap();
+        assert_eq!(fields[0].as_deref(), Some("1"));
+        assert_eq!(fields[2], None);
+
+        let inner = decode_row(fields[1].as_deref().unwrap()).unwrap();
+        assert_eq!(inner, strings(&[Some("2"), Some("x,\"y\"")]));
+    }
+
+    #[test]
+    fn row_as_output_by_postgres() {
+        let fields = decode_row(r#"(1,"(2,""x y"")",)"#).unwrap();
+        assert_eq!(fields, strings(&[Some("1"), Some(r#"(2,"x y")"#), None]));
+        assert_eq!(decode_row(fields[1].as_deref().unwrap()), Some(strings(&[Some("2"), Some("x y")])));
+
+        let fields = decode_row(r#"(,"(,""a""""b,c"")","")"#).unwrap();
+        assert_eq!(fields, strings(&[None, Some(r#"(,"a""b,c")"#), Some("")]));
+        assert_eq!(decode_row(fields[1].as_deref().unwrap()), Some(strings(&[None, Some("a\"b,c")])));
+
+        assert_eq!(decode_row(r#"(3,,"back\\slash")"#), Some(strings(&[Some("3"), None, Some("back\\slash")])));
+    }
+
+    #[test]
+    fn row_malformed() {
+        assert_eq!(decode_row("1,2"), None);
+        assert_eq!(decode_row("(1,2"), None);
+    }
+
+    /// Reads an escaped string constant the way Postgres lexes one, only if
+    /// it's the whole of the input, ie. nothing after it would be read as SQL
+    fn unquote_constant(literal: &str) -> Option<String> {
+        let mut chars = literal.strip_prefix("E'")?.chars();
+        let mut value = String::new();
+
+        loop {
+            match chars.next()? {
+                // Other escapes (eg. `\n`) would change the value
+                '\\' => match chars.next()? {
+                    c @ ('\\' | '\'') => value.push(c),
+                    _ => return None,
+                },
+                '\'' => match chars.next() {
+                    Some('\'') => value.push('\''),
+                    Some(_) => return None,
+                    None => return Some(value),
+                },
+                c => value.push(c),
+            }
+        }
+    }
+
+    #[test]
+    fn quote_constant_escaping() {
+        assert_eq!(quote_constant("plain"), "E'plain'");
+        assert_eq!(quote_constant("it's"), "E'it''s'");
+        assert_eq!(quote_constant("back\\slash"), r"E'back\\slash'");
+        assert_eq!(quote_constant(""), "E''");
+    }
+
+    #[test]
+    fn quote_constant_round_trip() {
+        let values = [
+            "plain",
+            "",
+            "it's",
+            "'",
+            "''",
+            "\\",
+            "\\'",
+            "\\\\'",
+            "trailing\\",
+            "\\n is not a newline",
+            "'); DROP TABLE band; --",
+            "' OR '1'='1",
+            "\\'; DROP TABLE band; --",
+            "E'nested'",
+            "$$dollar$$",
+            "new\nline",
+            "ünïcödé",
+        ];
+
+        for value in values {
+            assert_eq!(unquote_constant(&quote_constant(value)).as_deref(), Some(value), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn unquote_constant_rejects_trailing_sql() {
+        // Without escaping quotes, or backslashes before them, values could end
+        // the constant early
+        assert_eq!(unquote_constant("E'x' OR '1'='1'"), None);
+        assert_eq!(unquote_constant(r"E'\''; --'"), None);
+    }
+}
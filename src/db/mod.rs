@@ -1,5 +1,12 @@
+pub mod bytea;
+pub mod function;
+pub mod graph;
+pub mod import;
+pub mod literal;
 pub mod schema;
 pub mod table;
+pub mod tree;
 
+pub use function::Function;
 pub use schema::Schemas;
-pub use table::{Table, Column};
+pub use table::{Check, Column, Reference, Referrer, Table, UniqueKey};
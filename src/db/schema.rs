@@ -1,15 +1,18 @@
-use crate::Config;
+use crate::{config::RoleConfig, Config};
 use serde::Deserialize;
 use sqlx::{
     postgres::{
         types::Oid,
-        PgPool,
+        PgConnection,
     },
     types::Json,
 };
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct SchemaTableValue {
+    /// What sort of relation this is, eg. `table`, `view`, `materialized view`,
+    /// or `function` for functions listed alongside them
+    pub kind: String,
     pub name: String,
     pub oid: Oid,
 }
@@ -26,17 +29,32 @@ pub struct Schema {
 pub struct Schemas(Vec<Schema>);
 
 impl Schemas {
-    pub async fn load(pool: &PgPool, config: &Config) -> Schemas {
-        let schemas = sqlx::query_file_as!(
+    /// Loads every available table & function, where a config role may
+    /// narrow them down further
+    pub async fn load(conn: &mut PgConnection, config: &Config, role: Option<&RoleConfig>) -> Schemas {
+        let mut schemas = sqlx::query_file_as!(
             Schema,
             "queries/tables-by-schema.sql",
             &config.scope.include,
-            &config.scope.exclude
+            &config.scope.exclude,
+            &config.function_scope().include,
+            &config.function_scope().exclude
         )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .unwrap();
 
+        if let Some(role) = role {
+            for schema in &mut schemas {
+                schema.tables.retain(|t| match t.kind.as_str() {
+                    "function" => role.functions,
+                    _ => role.rights(&schema.name, &t.name).read,
+                });
+            }
+
+            schemas.retain(|s| !s.tables.is_empty());
+        }
+
         Schemas(schemas)
     }
 
@@ -47,4 +65,8 @@ impl Schemas {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
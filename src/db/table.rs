@@ -1,53 +1,253 @@
-use crate::Config;
+use crate::{config::RoleConfig, db::bytea, Config};
 use serde::Deserialize;
 use sqlx::{
     postgres::{
         types::Oid,
-        PgPool,
+        PgConnection,
     },
     types::Json,
 };
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct Check {
+    pub name: String,
+    pub definition: String,
+}
+
+/// The column referenced by a single-column foreign key
+#[derive(Clone, Debug, Deserialize)]
+pub struct Reference {
+    pub column: String,
+    pub table: Oid,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct ColumnValue {
     pub name: String,
+    /// Attributes of the column's composite type, loaded separately from the column
+    #[serde(default)]
+    pub attributes: Vec<Column>,
+    /// Check constraints inherited from the column's domain, if any
+    pub checks: Vec<Check>,
+    /// The relation describing the column's type, if it's a composite type
+    pub composite: Option<Oid>,
+    /// The underlying base type, even if the column uses a domain
     pub data_type: String,
+    /// The column's comment, if any
+    pub description: Option<String>,
+    pub domain: Option<String>,
+    /// The type of each element, if the column is an array
+    pub element_type: Option<String>,
+    /// Labels of an enum type (or an array's enum element type), in their sort order
+    pub enum_values: Option<Vec<String>>,
+    /// Whether the current role may insert into & update the column
+    /// respectively, which function arguments always can
+    #[serde(default = "granted")]
+    pub insertable: bool,
+    #[serde(default = "granted")]
+    pub updatable: bool,
     pub position: i32,
     pub nullable: bool,
     pub identity: Option<String>,
+    pub max_length: Option<i32>,
+    pub numeric_precision: Option<i32>,
+    pub numeric_scale: Option<i32>,
+    /// The type of the bounds, if the column is a range
+    pub range_subtype: Option<String>,
+    /// The column this one references, if it's a foreign key on its own
+    pub references: Option<Reference>,
+    /// The schema of the underlying base type, which isn't necessarily in the search path
+    pub type_schema: String,
     pub generated: Option<String>,
     pub expression: Option<String>,
 }
 
+fn granted() -> bool {
+    true
+}
+
 impl ColumnValue {
     pub fn always_generated(&self) -> bool {
         // TODO: Serialize as enums..?
         self.identity.as_deref() == Some("always") ||
-        self.identity.as_deref() == Some("stored")
+        self.generated.as_deref() == Some("stored")
+    }
+
+    /// The base type qualified by its schema, for casting values to, eg. `"ext"."ltree"`
+    pub fn cast_type(&self) -> String {
+        format!("\"{}\".\"{}\"", self.type_schema, self.data_type)
+    }
+
+    /// Whether the column holds plain strings, for which an empty string is a
+    /// value in itself rather than the lack of one
+    pub fn is_text(&self) -> bool {
+        matches!(self.data_type.as_str(), "text" | "varchar" | "bpchar" | "citext")
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.data_type == "json" || self.data_type == "jsonb"
+    }
+
+    /// Whether the database supplies the value, either as an identity
+    /// or as a generated column
+    pub fn is_generated(&self) -> bool {
+        self.identity.is_some() || self.generated.is_some()
+    }
+
+    /// The column's default expression; generated columns also have an
+    /// expression, but it's the generation expression rather than a default
+    pub fn default_expression(&self) -> Option<&str> {
+        match self.generated {
+            Some(_) => None,
+            None => self.expression.as_deref(),
+        }
+    }
+
+    /// Whether the column can be omitted and left to the database,
+    /// ie. it has a default or is `generated by default as identity`
+    pub fn has_default(&self) -> bool {
+        self.identity.as_deref() == Some("default") ||
+        self.default_expression().is_some()
     }
 }
 
 pub type Column = Json<ColumnValue>;
 
+/// A column of another table referencing this one, by a single-column foreign key
+#[derive(Clone, Debug, Deserialize)]
+pub struct Referrer {
+    pub table: Oid,
+    pub column: String,
+    /// The column of this table that's referenced
+    pub references: String,
+}
+
+/// A primary key or unique constraint
+#[derive(Clone, Debug, Deserialize)]
+pub struct UniqueKey {
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Table {
     pub columns: Vec<Column>,
+    /// What sort of relation this is, eg. `table`, `view`, `materialized view`
+    pub kind: String,
     pub name: String,
     pub oid: Oid,
     pub schema: String,
+    /// The relation's comment, if any
+    pub description: Option<String>,
+    /// Whether records can be inserted, updated & deleted respectively, which
+    /// for views depends on whether they're simple enough or have triggers, and
+    /// in any case on the current role's privileges (for any column)
+    pub insertable: bool,
+    pub updatable: bool,
+    pub deletable: bool,
+    pub unique_keys: Json<Vec<UniqueKey>>,
+    pub referenced_by: Json<Vec<Referrer>>,
 }
 
 impl Table {
-    pub async fn load(pool: &PgPool, config: &Config, oid: u32) -> Option<Table> {
-        sqlx::query_file_as!(
+    /// Columns referencing the table itself, eg. `parent_id`, by which its
+    /// records form a hierarchy
+    pub fn self_references(&self) -> impl Iterator<Item = &Column> {
+        self.columns.iter()
+            .filter(|c| c.references.as_ref().is_some_and(|r| r.table == self.oid))
+    }
+
+    /// The column best describing a record to people, ie. its first text column
+    pub fn label_column(&self) -> Option<&Column> {
+        self.columns.iter().find(|c| c.is_text())
+    }
+
+    /// Selects every column as text for display, except binary columns which
+    /// are only summarized rather than selected whole
+    pub fn select_list(&self) -> String {
+        self.columns.iter()
+            .map(|c| match c.data_type.as_str() {
+                "bytea" => format!("{} AS \"{}\"", bytea::summary_expression(&c.name), c.name),
+                _ => format!("\"{}\"::text", c.name),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Each record as a JSON object, keyed by column name, which also works in a
+    /// `RETURNING` clause since the table is referred to by its own name. Only
+    /// the loaded columns are listed, as the role may not select the others.
+    pub fn json_expression(&self) -> String {
+        let columns = self.columns.iter()
+            .map(|c| format!("\"{}\".\"{}\"", self.name, c.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("(SELECT to_jsonb(r) FROM (SELECT {columns}) r)")
+    }
+
+    /// Loads a table, narrowed down to what the config role allows, if any
+    pub async fn load(conn: &mut PgConnection, config: &Config, role: Option<&RoleConfig>, oid: u32) -> Option<Table> {
+        let table = Self::load_relation(&mut *conn, config, oid, false).await?;
+
+        match role {
+            Some(role) => table.restrict(role),
+            None => Some(table),
+        }
+    }
+
+    /// Leaves out tables the role can't read and columns hidden from it, and
+    /// makes its read-only columns neither insertable nor updatable, the same
+    /// way as lacking privileges to them
+    fn restrict(mut self, role: &RoleConfig) -> Option<Table> {
+        let rights = role.rights(&self.schema, &self.name);
+
+        if !rights.read {
+            return None;
+        }
+
+        self.columns.retain(|c| !role.hidden(&self.schema, &self.name, &c.name));
+
+        for column in &mut self.columns {
+            if role.readonly(&self.schema, &self.name, &column.name) {
+                column.insertable = false;
+                column.updatable = false;
+            }
+        }
+
+        // Records can't be updated without any columns to set
+        self.insertable &= rights.create;
+        self.updatable &= rights.update && self.columns.iter().any(|c| c.updatable);
+        self.deletable &= rights.delete;
+
+        Some(self)
+    }
+
+    /// Loads a table, or the composite type describing a column, along with the
+    /// attributes of any composite types its own columns use
+    pub(crate) async fn load_relation(conn: &mut PgConnection, config: &Config, oid: u32, composite: bool) -> Option<Table> {
+        let mut table = sqlx::query_file_as!(
             Table,
             "queries/table-details.sql",
             &config.scope.include,
             &config.scope.exclude,
-            Oid(oid)
+            Oid(oid),
+            composite
         )
-            .fetch_optional(pool)
+            .fetch_optional(&mut *conn)
             .await
-            .unwrap()
+            .unwrap()?;
+
+        for column in &mut table.columns {
+            if let Some(Oid(type_oid)) = column.composite {
+                // Postgres doesn't allow composite types to contain themselves,
+                // so this always terminates
+                let composite = Box::pin(Self::load_relation(&mut *conn, config, type_oid, true)).await;
+
+                column.attributes = composite.map(|c| c.columns).unwrap_or_default();
+            }
+        }
+
+        Some(table)
     }
 }
@@ -0,0 +1,289 @@
+//! Browsing tables as hierarchies of their records, one level at a time. Records
+//! are arranged either by an `ltree` column, where any paths below a path (`<@`)
+//! are its descendants, or by a column referencing the table itself (eg.
+//! `parent_id`), walked with recursive queries.
+
+use crate::db::{Column, Reference, Table};
+use sqlx::{FromRow, PgConnection};
+
+/// The most records (or paths) offered as parents to choose from, beyond which
+/// parents are entered by their value without any suggestions
+pub const MAX_PARENTS: usize = 500;
+
+#[derive(Debug, FromRow)]
+pub struct Node {
+    /// Identifies the node as a parent, ie. its path or referenced value
+    pub key: String,
+    pub label: String,
+    /// How many records are below this one, at any depth
+    pub descendants: i64,
+    /// The record at exactly this node; paths don't need a record of their
+    /// own for their descendants to be browsable
+    pub record_id: Option<String>,
+}
+
+/// A column by which records can be arranged in a hierarchy
+pub enum Hierarchy<'a> {
+    Path(&'a Column),
+    Parent(&'a Column, &'a Reference),
+}
+
+impl<'a> Hierarchy<'a> {
+    pub fn of(table: &'a Table, column: &'a Column) -> Option<Self> {
+        match &column.references {
+            Some(reference) if reference.table == table.oid => Some(Self::Parent(column, reference)),
+            _ if column.data_type == "ltree" => Some(Self::Path(column)),
+            _ => None,
+        }
+    }
+
+    /// The nodes one level below the given parent, where an empty parent is the root
+    pub async fn children(&self, conn: &mut PgConnection, table: &Table, parent: &str) -> Result<Vec<Node>, sqlx::Error> {
+        let statement = match self {
+            Self::Path(column) => {
+                let ltree = column.type_schema.as_str();
+
+                // TODO: Don't rely on `id` field
+                format!(r#"
+                    WITH parent AS (
+                        SELECT $1::"{ltree}"."ltree" AS path, "{ltree}".nlevel($1::"{ltree}"."ltree") + 1 AS depth
+                    )
+                    SELECT
+                        "{ltree}".subpath(t."{column}", 0, parent.depth)::text AS key,
+                        "{ltree}".subpath(t."{column}", parent.depth - 1, 1)::text AS label,
+                        count(*) FILTER (WHERE "{ltree}".nlevel(t."{column}") > parent.depth) AS descendants,
+                        min(t.id::text) FILTER (WHERE "{ltree}".nlevel(t."{column}") = parent.depth) AS record_id
+                    FROM "{}"."{}" t, parent
+                    WHERE
+                        t."{column}" OPERATOR("{ltree}".<@) parent.path AND
+                        "{ltree}".nlevel(t."{column}") >= parent.depth
+                    GROUP BY 1, 2
+                    ORDER BY 1
+                    "#,
+                    table.schema,
+                    table.name,
+                    column = column.name,
+                )
+            }
+            Self::Parent(column, reference) => {
+                let label = table.label_column().map_or(&reference.column, |c| &c.name);
+
+                // Pairs are only visited once, so this terminates even if the
+                // records already form a cycle
+                format!(r#"
+                    WITH RECURSIVE descendants AS (
+                        SELECT t."{key}" AS root, t."{key}" AS node
+                        FROM "{schema}"."{table}" t
+                        WHERE CASE WHEN $1 = '' THEN t."{column}" IS NULL ELSE t."{column}"::text = $1 END
+
+                        UNION
+
+                        SELECT d.root, t."{key}"
+                        FROM descendants d
+                        JOIN "{schema}"."{table}" t ON t."{column}" = d.node
+                    )
+                    SELECT
+                        t."{key}"::text AS key,
+                        coalesce(t."{label}"::text, '') AS label,
+                        count(*) - 1 AS descendants,
+                        t.id::text AS record_id
+                    FROM descendants d
+                    JOIN "{schema}"."{table}" t ON t."{key}" = d.root
+                    GROUP BY t."{key}", t."{label}", t.id
+                    ORDER BY 2, 1
+                    "#,
+                    schema = table.schema,
+                    table = table.name,
+                    column = column.name,
+                    key = reference.column,
+                )
+            }
+        };
+
+        sqlx::query_as(&statement)
+            .bind(parent)
+            .fetch_all(&mut *conn)
+            .await
+    }
+
+    /// The nodes from the root down to (and including) the given parent, as
+    /// (key, label) pairs
+    pub async fn ancestors(&self, conn: &mut PgConnection, table: &Table, parent: &str) -> Result<Vec<(String, String)>, sqlx::Error> {
+        match self {
+            Self::Path(_) => {
+                let mut ancestors: Vec<(String, String)> = Vec::new();
+
+                for label in parent.split('.').filter(|l| !l.is_empty()) {
+                    let key = match ancestors.last() {
+                        Some((path, _)) => format!("{path}.{label}"),
+                        None => label.to_owned(),
+                    };
+
+                    ancestors.push((key, label.to_owned()));
+                }
+
+                Ok(ancestors)
+            }
+            Self::Parent(column, reference) => {
+                let label = table.label_column().map_or(&reference.column, |c| &c.name);
+
+                let statement = format!(r#"
+                    WITH RECURSIVE ancestors AS (
+                        SELECT t."{key}", t."{column}", t."{label}"::text AS label, ARRAY[t."{key}"] AS visited
+                        FROM "{schema}"."{table}" t
+                        WHERE t."{key}"::text = $1
+
+                        UNION ALL
+
+                        SELECT t."{key}", t."{column}", t."{label}"::text, a.visited || t."{key}"
+                        FROM ancestors a
+                        JOIN "{schema}"."{table}" t ON t."{key}" = a."{column}"
+                        WHERE NOT t."{key}" = ANY(a.visited)
+                    )
+                    SELECT "{key}"::text, coalesce(label, '')
+                    FROM ancestors
+                    ORDER BY cardinality(visited) DESC
+                    "#,
+                    schema = table.schema,
+                    table = table.name,
+                    column = column.name,
+                    key = reference.column,
+                );
+
+                sqlx::query_as(&statement)
+                    .bind(parent)
+                    .fetch_all(&mut *conn)
+                    .await
+            }
+        }
+    }
+}
+
+/// Every path that could be the parent of a new path, ie. existing paths
+/// along with all of their ancestors. `None` if there are more than
+/// `MAX_PARENTS` of them.
+pub async fn paths(
+    conn: &mut PgConnection,
+    table: &Table,
+    column: &Column,
+) -> Result<Option<Vec<String>>, sqlx::Error> {
+    let ltree = column.type_schema.as_str();
+
+    let statement = format!(r#"
+        SELECT DISTINCT "{ltree}".subpath(t."{column}", 0, level)::text
+        FROM "{}"."{}" t, generate_series(1, "{ltree}".nlevel(t."{column}")) AS level
+        ORDER BY 1
+        LIMIT {limit}
+        "#,
+        table.schema,
+        table.name,
+        column = column.name,
+        // One more than the most offered, to tell whether there are too many
+        limit = MAX_PARENTS + 1,
+    );
+
+    let paths: Vec<String> = sqlx::query_scalar(&statement)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(Some(paths).filter(|p| p.len() <= MAX_PARENTS))
+}
+
+/// Records that a record could be moved below, as (value, label) pairs, ie.
+/// every record except itself and its own descendants, or every record for
+/// a record that's yet to be created. `None` if there are more than
+/// `MAX_PARENTS` of them.
+pub async fn eligible_parents(
+    conn: &mut PgConnection,
+    table: &Table,
+    column: &Column,
+    reference: &Reference,
+    record_id: Option<i64>,
+) -> Result<Option<Vec<(String, String)>>, sqlx::Error> {
+    let label = table.label_column().map_or(&reference.column, |c| &c.name);
+
+    // TODO: Don't rely on `id` field
+    let statement = format!(r#"
+        WITH RECURSIVE descendants AS (
+            SELECT t."{key}" AS node
+            FROM "{schema}"."{table}" t
+            WHERE t.id = $1
+
+            UNION
+
+            SELECT t."{key}"
+            FROM descendants d
+            JOIN "{schema}"."{table}" t ON t."{column}" = d.node
+        )
+        SELECT t."{key}"::text, coalesce(t."{label}"::text, '')
+        FROM "{schema}"."{table}" t
+        WHERE t."{key}" NOT IN (SELECT node FROM descendants)
+        ORDER BY 2, 1
+        LIMIT {limit}
+        "#,
+        schema = table.schema,
+        table = table.name,
+        column = column.name,
+        key = reference.column,
+        // One more than the most offered, to tell whether there are too many
+        limit = MAX_PARENTS + 1,
+    );
+
+    let parents: Vec<(String, String)> = sqlx::query_as(&statement)
+        .bind(record_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(Some(parents).filter(|p| p.len() <= MAX_PARENTS))
+}
+
+/// Serializes moving records within a table until the transaction ends, so
+/// that records moved below each other at the same time can't both pass the
+/// check for cycles; only writes made through here take the lock
+pub async fn lock(conn: &mut PgConnection, table: &Table) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(i64::from(table.oid.0))
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Whether moving records below the given parent would make any of them its
+/// own ancestor, ie. the parent is one of the records or their descendants
+pub async fn creates_cycle(
+    conn: &mut PgConnection,
+    table: &Table,
+    column: &Column,
+    reference: &Reference,
+    record_ids: &[i64],
+    parent: &str,
+) -> Result<bool, sqlx::Error> {
+    // TODO: Don't rely on `id` field
+    let statement = format!(r#"
+        WITH RECURSIVE descendants AS (
+            SELECT t."{key}" AS node
+            FROM "{schema}"."{table}" t
+            WHERE t.id = ANY($1)
+
+            UNION
+
+            SELECT t."{key}"
+            FROM descendants d
+            JOIN "{schema}"."{table}" t ON t."{column}" = d.node
+        )
+        SELECT EXISTS (SELECT FROM descendants WHERE node::text = ($2::{cast})::text)
+        "#,
+        schema = table.schema,
+        table = table.name,
+        column = column.name,
+        key = reference.column,
+        cast = column.cast_type(),
+    );
+
+    sqlx::query_scalar(&statement)
+        .bind(record_ids)
+        .bind(parent)
+        .fetch_one(&mut *conn)
+        .await
+}
@@ -1,3 +1,5 @@
+pub mod api;
+pub mod auth;
 pub mod config;
 pub mod db;
 pub mod state;
@@ -1,19 +1,39 @@
 use actix_files::Files;
+use actix_multipart::Multipart;
 use actix_web::{
+    cookie::{Cookie, SameSite, time::Duration as CookieDuration},
+    dev::{Service, ServiceRequest},
     middleware::DefaultHeaders,
-    web::{Data, Form, Path, Query, scope},
+    error::{ErrorBadRequest, ErrorInternalServerError, InternalError},
+    http::Method,
+    web::{Bytes, Data, Form, Json, JsonConfig, Path, Payload, Query, scope},
     App,
     Either,
+    Error as WebError,
+    FromRequest,
+    HttpMessage,
+    HttpRequest,
     HttpResponse,
     HttpServer,
+    ResponseError,
+    delete,
     get,
+    patch,
     post,
 };
+use futures_util::{
+    future::{self, Either as FutureEither},
+    stream::{self, Stream},
+    StreamExt,
+    TryStreamExt,
+};
 use maud::{html, DOCTYPE, Markup};
-use mngr::{state::*, db, ui};
+use mngr::{state::*, api, auth, db, ui};
 use serde::Deserialize;
-use sqlx::Error as SqlError;
-use std::{collections::HashMap, env, io};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use std::{env, io, time::Duration};
+use tokio::sync::{mpsc, oneshot};
 
 const YEAR_IN_SECONDS: isize = 60 * 60 * 24 * 365;
 
@@ -24,9 +44,14 @@ struct RecordsPath {
 
 #[derive(Deserialize)]
 struct RecordsParams {
+    /// Starting from 1, though only the JSON API paginates so far
     page: Option<i64>,
+    /// Records per page, for the JSON API
+    per_page: Option<i64>,
     sort_column: Option<String>,
     sort_direction: Option<String>,
+    /// Either `csv` or `tsv`, when exporting
+    format: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -35,9 +60,142 @@ struct RecordPath {
     record_id: i64,
 }
 
-async fn page(state: &State, content: Markup) -> Markup {
-    let schemas = mngr::db::Schemas::load(&state.pool, &state.config).await;
+#[derive(Deserialize)]
+struct GraphParams {
+    /// How far away related records are followed
+    depth: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SqlParams {
+    /// Skip records that would conflict with existing ones
+    on_conflict: Option<String>,
+    /// Leave identities to the database the records are inserted into
+    omit_identities: Option<String>,
+    /// Download the statements as a file, rather than showing them
+    download: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FilePath {
+    table_oid: u32,
+    record_id: i64,
+    column: String,
+}
+
+#[derive(Deserialize)]
+struct TreePath {
+    table_oid: u32,
+    column: String,
+}
+
+#[derive(Deserialize)]
+struct TreeParams {
+    parent: Option<String>,
+    /// Only render the nodes, for expanding a node of a tree already shown
+    partial: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ActionPath {
+    table_oid: u32,
+    action: usize,
+}
+
+#[derive(Deserialize)]
+struct FunctionPath {
+    function_oid: u32,
+}
+
+#[derive(Deserialize)]
+struct LoginParams {
+    back: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    name: String,
+    password: String,
+    back: Option<String>,
+}
 
+#[derive(Deserialize)]
+struct FileParams {
+    /// Show the file in the browser (if it's an image) rather than downloading it
+    inline: Option<String>,
+}
+
+/// A submission that was rejected, to be shown back to the user along with why
+struct Rejected<'a> {
+    values: &'a [(String, ui::form::Value)],
+    error: ui::form::Error,
+}
+
+/// Records chosen from the records list, either one by one or as all records
+/// matching the list's filters, which is passed along in query strings from the
+/// preview of a bulk operation to the operation itself
+struct Selection {
+    ids: Vec<String>,
+    /// The list's filters, when all records matching them are selected
+    filters: Option<Vec<(String, String)>>,
+    /// Where to return to afterwards, which is only ever a local path, so the
+    /// selection can't be used to redirect elsewhere
+    back: String,
+}
+
+impl Selection {
+    fn new(table: &db::Table, query: &[(String, String)]) -> Self {
+        let all = query.iter().any(|(k, v)| k == "all" && v == "true");
+
+        Self {
+            ids: query.iter()
+                .filter(|(k, _)| k == "id")
+                .map(|(_, v)| v.to_owned())
+                .collect(),
+            filters: all.then(|| query.iter()
+                .filter(|(k, _)| k.starts_with(ui::table::FILTER_PREFIX))
+                .cloned()
+                .collect()),
+            back: local_path(query.iter().find(|(k, _)| k == "back").map(|(_, v)| v.as_str()))
+                .map_or_else(|| format!("/tables/{}/records", table.oid.0), str::to_owned),
+        }
+    }
+
+    fn query(&self) -> Vec<(&str, &str)> {
+        let mut query = match &self.filters {
+            Some(filters) => std::iter::once(("all", "true"))
+                .chain(filters.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+                .collect(),
+            None => self.ids.iter().map(|id| ("id", id.as_str())).collect::<Vec<_>>(),
+        };
+
+        query.push(("back", &self.back));
+        query
+    }
+
+    /// Conditions matching the selected records, along with their values to bind,
+    /// numbering placeholders after the first `offset` params
+    fn conditions(&self, table: &db::Table, offset: usize) -> (Vec<String>, Vec<&String>) {
+        if let Some(filters) = &self.filters {
+            return filter_conditions(table, filters, offset);
+        }
+
+        // Selecting nothing matches nothing, rather than everything
+        if self.ids.is_empty() {
+            return (vec!["false".to_owned()], Vec::new());
+        }
+
+        let id_type = id_type(table);
+        let placeholders = (1..=self.ids.len())
+            .map(|i| format!("${}::{id_type}", offset + i))
+            .collect::<Vec<_>>();
+
+        // TODO: Don't rely on `id` field
+        (vec![format!("id IN ({})", placeholders.join(", "))], self.ids.iter().collect())
+    }
+}
+
+fn layout(sidebar: Option<Markup>, content: Markup) -> Markup {
     html! {
         (DOCTYPE)
         head {
@@ -45,9 +203,29 @@ async fn page(state: &State, content: Markup) -> Markup {
             link rel="stylesheet" type="text/css" href="/static/css/main.css";
         }
         body {
-            c-sidebar {
-                h1 { "mngr" }
-                nav {
+            @if let Some(sidebar) = sidebar {
+                (sidebar)
+            }
+            c-content {
+                (content)
+            }
+        }
+    }
+}
+
+async fn page(state: &State, content: Markup) -> Markup {
+    // The content is still shown when the sidebar can't be loaded, eg. when the
+    // pool is exhausted, with the reason in its place
+    let schemas = match state.begin().await {
+        Ok(mut transaction) => Ok(mngr::db::Schemas::load(&mut transaction, &state.config, state.permissions()).await),
+        Err(e) => Err(e),
+    };
+
+    layout(Some(html! {
+        c-sidebar {
+            h1 { "mngr" }
+            @match &schemas {
+                Ok(schemas) => nav {
                     @let show_schema = schemas.len() > 1;
 
                     @for schema in schemas.iter() {
@@ -57,25 +235,36 @@ async fn page(state: &State, content: Markup) -> Markup {
                             }
                             menu {
                                 @for table in &schema.tables {
+                                    @let href = match table.kind.as_str() {
+                                        "function" => format!("/functions/{}", table.oid.0),
+                                        _ => format!("/tables/{}/records", table.oid.0),
+                                    };
+
                                     li {
                                         a
-                                            href=(format!("/tables/{}/records", table.oid.0))
+                                            href=(href)
                                             data-table-oid=(table.oid.0)
                                         {
                                             (table.name)
                                         }
+                                        @if table.kind != "table" {
+                                            small.kind { (table.kind) }
+                                        }
                                     }
                                 }
                             }
                         }
                     }
-                }
+                },
+                Err(e) => output class="error" {
+                    pre { (format!("{e:#?}")) }
+                },
             }
-            c-content {
-                (content)
+            form.logout method="post" action="/logout" {
+                button type="submit" { "Log out" }
             }
         }
-    }
+    }), content)
 }
 
 async fn records_page(
@@ -92,9 +281,30 @@ async fn records_page(
                         "All Records"
                     }
                 }
-                li {
-                    a href=(format!("/tables/{}/records/new", table.oid.0)) {
-                        "New Record"
+                @if table.insertable {
+                    li {
+                        a href=(format!("/tables/{}/records/new", table.oid.0)) {
+                            "New Record"
+                        }
+                    }
+                    li {
+                        a href=(format!("/tables/{}/import", table.oid.0)) {
+                            "Import"
+                        }
+                    }
+                }
+                @for column in table.columns.iter().filter(|c| db::tree::Hierarchy::of(table, c).is_some()) {
+                    li {
+                        a href=(format!("/tables/{}/tree/{}", table.oid.0, column.name)) {
+                            "Tree by " (column.name)
+                        }
+                    }
+                }
+                @if refreshable(state, table) {
+                    li {
+                        form method="post" action=(format!("/tables/{}/refresh", table.oid.0)) {
+                            button type="submit" { "Refresh" }
+                        }
                     }
                 }
             }
@@ -103,8 +313,98 @@ async fn records_page(
     }).await
 }
 
+async fn function_page(
+    state: &State,
+    function: &db::Function,
+    content: Markup,
+) -> Markup {
+    page(state, html! {
+        header {
+            h2 { (function.name) }
+            @if let Some(description) = &function.description {
+                p { (description) }
+            }
+        }
+        (content)
+    }).await
+}
+
+#[get("/")]
+async fn get_index(state: State) -> Markup {
+    page(&state, html! {}).await
+}
+
+#[get("/login")]
+async fn get_login(params: Query<LoginParams>) -> Markup {
+    let back = local_path(params.back.as_deref()).unwrap_or("/");
+
+    layout(None, html! { (ui::login::Login::new(back)) })
+}
+
+#[post("/login")]
+async fn post_login(
+    form: Form<LoginForm>,
+    state: State,
+) -> Either<HttpResponse, Markup> {
+    let back = local_path(form.back.as_deref()).unwrap_or("/");
+
+    let user = match auth::authenticate(&state.config, &form.name, &form.password) {
+        Some(user) => user,
+        None => {
+            let ui_login = ui::login::Login::new(back).error("Incorrect name or password");
+
+            return Either::Right(layout(None, html! { (ui_login) }));
+        }
+    };
+
+    // Roles & session variables are only checked once they're used, so this is
+    // a better time to find out
+    if let Err(e) = state.acting_as(Some(&user)).begin().await {
+        let error = format!("Can't connect as {}: {e}", user.name);
+        let ui_login = ui::login::Login::new(back).error(&error);
+
+        return Either::Right(layout(None, html! { (ui_login) }));
+    }
+
+    let max_age = state.config.sessions.max_age;
+    let token = state.sessions.start(user, Duration::from_secs(max_age));
+
+    // Lax, so that other sites can't submit forms as the user
+    let cookie = Cookie::build(auth::SESSION_COOKIE, token)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(CookieDuration::seconds(max_age as i64))
+        .finish();
+
+    Either::Left(HttpResponse::SeeOther()
+        .insert_header(("Location", back))
+        .cookie(cookie)
+        .finish())
+}
+
+#[post("/logout")]
+async fn post_logout(req: HttpRequest, state: State) -> HttpResponse {
+    if let Some(cookie) = req.cookie(auth::SESSION_COOKIE) {
+        state.sessions.end(cookie.value());
+    }
+
+    let mut cookie = Cookie::build(auth::SESSION_COOKIE, "").path("/").finish();
+    cookie.make_removal();
+
+    HttpResponse::SeeOther()
+        .insert_header(("Location", "/login"))
+        .cookie(cookie)
+        .finish()
+}
+
 #[get("/debug/state")]
-async fn get_state(state: Data<State>) -> Markup {
+async fn get_state(state: State) -> Markup {
+    // Roles limiting users shouldn't be got around by reading the config
+    if state.permissions().is_some() {
+        return not_found(&state).await;
+    }
+
     let state = format!("{:#?}", state);
 
     html! {
@@ -116,192 +416,1477 @@ async fn get_state(state: Data<State>) -> Markup {
 async fn get_table_records(
     path: Path<RecordsPath>,
     params: Query<RecordsParams>,
-    state: Data<State>,
-) -> Markup {
+    query: Query<Vec<(String, String)>>,
+    state: State,
+) -> Result<Markup, WebError> {
     // TODO: Implement an extractor for this
-    match load_table(&state, path.table_oid).await {
-        Some(table) => render_records(&state, &table, &params).await,
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) => render_records(&state, &table, &params, &query).await,
         None => not_found(&state).await,
-    }
+    })
+}
+
+#[get("/tables/{table_oid}/records/export")]
+async fn get_table_records_export(
+    path: Path<RecordsPath>,
+    params: Query<RecordsParams>,
+    query: Query<Vec<(String, String)>>,
+    state: State,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) => export_records(&state, &table, &params, &query).await,
+        None => Either::Right(not_found(&state).await),
+    })
 }
 
 #[get("/tables/{table_oid}/records/new")]
 async fn get_table_records_new(
     path: Path<RecordsPath>,
-    state: Data<State>,
-) -> Markup {
-    match load_table(&state, path.table_oid).await {
+    state: State,
+) -> Result<Markup, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
         Some(table) => render_new_record(&state, &table, None).await,
         None => not_found(&state).await,
-    }
+    })
 }
 
 #[post("/tables/{table_oid}/records/new")]
 async fn post_table_records_new(
     path: Path<RecordsPath>,
-    state: Data<State>,
-    form: Form<HashMap<String, String>>,
-) -> Either<HttpResponse, Markup> {
-    match load_table(&state, path.table_oid).await {
-        Some(table) => create_new_record(&state, &table, &form).await,
+    state: State,
+    req: HttpRequest,
+    payload: Payload,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if !table.insertable => Either::Left(read_only()),
+        Some(table) => {
+            let values = read_form(&state, &table.columns, &req, payload).await?;
+
+            create_new_record(&state, &table, values).await
+        }
         None => Either::Right(not_found(&state).await),
-    }
+    })
 }
 
 #[get("/tables/{table_oid}/records/{record_id}/edit")]
 async fn get_table_record_edit(
     path: Path<RecordPath>,
-    state: Data<State>,
-) -> Markup {
-    match load_table(&state, path.table_oid).await {
+    state: State,
+) -> Result<Markup, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
         Some(table) => render_edit_record(&state, &table, path.record_id, None).await,
         None => not_found(&state).await,
-    }
+    })
 }
 
 #[post("/tables/{table_oid}/records/{record_id}/edit")]
 async fn post_table_record_edit(
     path: Path<RecordPath>,
-    state: Data<State>,
-    form: Form<HashMap<String, String>>,
-) -> Either<HttpResponse, Markup> {
-    match load_table(&state, path.table_oid).await {
-        Some(table) => update_record(&state, &table, path.record_id, &form).await,
+    state: State,
+    req: HttpRequest,
+    payload: Payload,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if !table.updatable => Either::Left(read_only()),
+        Some(table) => {
+            let values = read_form(&state, &table.columns, &req, payload).await?;
+
+            update_record(&state, &table, path.record_id, values).await
+        }
         None => Either::Right(not_found(&state).await),
-    }
+    })
 }
 
-async fn load_table(
-    state: &State,
-    table_oid: u32,
-) -> Option<db::Table> {
-    db::Table::load(&state.pool, &state.config, table_oid).await
+#[post("/tables/{table_oid}/refresh")]
+async fn post_table_refresh(
+    path: Path<RecordsPath>,
+    state: State,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if refreshable(&state, &table) => refresh_view(&state, &table).await,
+        _ => Either::Right(not_found(&state).await),
+    })
 }
 
-async fn render_records(
-    state: &State,
-    table: &db::Table,
-    params: &RecordsParams,
-) -> Markup {
-    let sort_column = match &params.sort_column {
-        Some(c1) => {
-            table.columns.iter().find(|c2| c1 == &c2.name).unwrap()
-        },
-        None => {
-            // Assumes query returns columns sorted by their ordinal position, in which case
-            // also assume the first column is probably the primary key and should be used
-            // for ordering by default.
-            table.columns.first().unwrap()
-        },
+#[post("/tables/{table_oid}/actions/{action}")]
+async fn post_table_action(
+    path: Path<ActionPath>,
+    form: Form<Vec<(String, String)>>,
+    state: State,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    let table = match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) => table,
+        None => return Ok(Either::Right(not_found(&state).await)),
     };
 
-    let sort_direction = params.sort_direction.as_ref().map_or("asc", |sd| sd.as_str());
+    Ok(match table_actions(&state, &table).get(path.action) {
+        Some(action) => Either::Left(run_action(&state, &table, action, &form).await),
+        None => Either::Right(not_found(&state).await),
+    })
+}
 
-    // TODO: This has become a common pattern, so.. abstract. Maybe think about
-    // adding this as a pre-generated field on the `Table` struct?
-    let columns = table.columns.iter()
-        .map(|c| format!("\"{}\"::text", c.name))
-        .collect::<Vec<_>>()
-        .join(", ");
+#[get("/tables/{table_oid}/bulk/edit")]
+async fn get_table_bulk_edit(
+    path: Path<RecordsPath>,
+    query: Query<Vec<(String, String)>>,
+    state: State,
+) -> Result<Markup, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if table.updatable => {
+            let selection = Selection::new(&table, &query);
+            let column = bulk_column(&table, &query);
 
-    // TODO: Incorporate limit & pagination params
-    let statement = format!(r#"
-        SELECT {} FROM "{}"."{}"
-        ORDER BY "{}"::{} {}
-        LIMIT 50
-        "#,
-        columns,
-        table.schema,
-        table.name,
-        sort_column.name,
-        sort_column.data_type,
-        sort_direction,
-    );
+            render_bulk_edit(&state, &table, &selection, column, None).await
+        }
+        _ => not_found(&state).await,
+    })
+}
 
-    let result = sqlx::query(&statement)
-        .fetch_all(&state.pool)
-        .await;
+#[post("/tables/{table_oid}/bulk/edit")]
+async fn post_table_bulk_edit(
+    path: Path<RecordsPath>,
+    query: Query<Vec<(String, String)>>,
+    state: State,
+    req: HttpRequest,
+    payload: Payload,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if !table.updatable => Either::Left(read_only()),
+        Some(table) => match bulk_column(&table, &query) {
+            Some(column) => {
+                let selection = Selection::new(&table, &query);
+                let values = read_form(&state, std::slice::from_ref(column), &req, payload).await?;
 
-    match result {
-        Ok(rows) => {
-            let ui_table = ui::table::Table::new(&table, rows);
-            records_page(state, table, html! {
-                (ui_table)
-            }).await
+                bulk_update(&state, &table, &selection, column, values).await
+            }
+            None => Either::Right(not_found(&state).await),
+        },
+        None => Either::Right(not_found(&state).await),
+    })
+}
+
+#[get("/tables/{table_oid}/bulk/delete")]
+async fn get_table_bulk_delete(
+    path: Path<RecordsPath>,
+    query: Query<Vec<(String, String)>>,
+    state: State,
+) -> Result<Markup, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if table.deletable => {
+            let selection = Selection::new(&table, &query);
+
+            render_bulk_delete(&state, &table, &selection).await
         }
-        Err(e) => {
-            records_page(state, table, html! {
-                pre {
-                    (statement)
-                }
-                pre {
-                    (format!("{:#?}", e))
-                }
-            }).await
+        _ => not_found(&state).await,
+    })
+}
+
+#[post("/tables/{table_oid}/bulk/delete")]
+async fn post_table_bulk_delete(
+    path: Path<RecordsPath>,
+    query: Query<Vec<(String, String)>>,
+    state: State,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if !table.deletable => Either::Left(read_only()),
+        Some(table) => {
+            let selection = Selection::new(&table, &query);
+
+            Either::Left(bulk_delete(&state, &table, &selection).await)
         }
-    }
+        None => Either::Right(not_found(&state).await),
+    })
+}
+
+#[get("/tables/{table_oid}/bulk/sql")]
+async fn get_table_bulk_sql(
+    path: Path<RecordsPath>,
+    params: Query<SqlParams>,
+    query: Query<Vec<(String, String)>>,
+    state: State,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) => {
+            let selection = Selection::new(&table, &query);
 
+            render_bulk_sql(&state, &table, &selection, &params).await
+        }
+        None => Either::Right(not_found(&state).await),
+    })
 }
 
+#[get("/tables/{table_oid}/import")]
+async fn get_table_import(
+    path: Path<RecordsPath>,
+    state: State,
+) -> Result<Markup, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if table.insertable => {
+            records_page(&state, &table, html! { (ui::import::Upload::new(&table, None)) }).await
+        }
+        _ => not_found(&state).await,
+    })
+}
 
-async fn render_new_record(
-    state: &State,
-    table: &db::Table,
-    error: Option<SqlError>,
-) -> Markup {
-    let mut ui_form = ui::form::Form::from(table.columns.as_slice())
-        .method("post")
-        .action(&format!("/tables/{}/records/new", table.oid.0));
+#[post("/tables/{table_oid}/import")]
+async fn post_table_import(
+    path: Path<RecordsPath>,
+    state: State,
+    req: HttpRequest,
+    payload: Payload,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if !table.insertable => Either::Left(read_only()),
+        Some(table) => {
+            let (form, files) = read_multipart(&state, &req, payload).await?;
 
-    if let Some(e) = error {
-        ui_form = ui_form.error(e);
-    }
+            Either::Right(upload_import(&state, &req, &table, &form, files).await)
+        }
+        None => Either::Right(not_found(&state).await),
+    })
+}
 
-    records_page(state, table, html! { (ui_form ) }).await
+#[post("/tables/{table_oid}/import/run")]
+async fn post_table_import_run(
+    path: Path<RecordsPath>,
+    form: Form<Vec<(String, String)>>,
+    state: State,
+    req: HttpRequest,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if !table.insertable => Either::Left(read_only()),
+        Some(table) => run_import(&state, &req, &table, &form).await,
+        None => Either::Right(not_found(&state).await),
+    })
 }
 
+#[get("/tables/{table_oid}/records/{record_id}/export")]
+async fn get_table_record_export(
+    path: Path<RecordPath>,
+    params: Query<GraphParams>,
+    state: State,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) => export_graph(&state, &table, path.record_id, &params).await,
+        None => Either::Right(not_found(&state).await),
+    })
+}
 
-async fn render_edit_record(
-    state: &State,
-    table: &db::Table,
-    record_id: i64, // TODO: Dynamic primary key column, not just "id"
-    error: Option<SqlError>,
-) -> Markup {
-    // TODO: Abstract, this is duplicate
-    let columns = table.columns.iter()
-        .map(|c| format!("\"{}\"::text", c.name))
-        .collect::<Vec<_>>()
-        .join(", ");
+#[post("/tables/{table_oid}/import/graph")]
+async fn post_table_import_graph(
+    path: Path<RecordsPath>,
+    state: State,
+    req: HttpRequest,
+    payload: Payload,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) if !table.insertable => Either::Left(read_only()),
+        Some(table) => {
+            let (_, files) = read_multipart(&state, &req, payload).await?;
 
-    // TODO: Don't rely on `id` field
-    let statement = format!(r#"
-        SELECT {} FROM "{}"."{}"
-        WHERE id = $1
-        "#,
-        columns,
-        table.schema,
-        table.name,
-    );
+            import_graph(&state, &table, files).await
+        }
+        None => Either::Right(not_found(&state).await),
+    })
+}
 
-    let result = sqlx::query(&statement)
-        .bind(record_id)
-        .fetch_one(&state.pool)
-        .await;
+#[get("/tables/{table_oid}/records/{record_id}/files/{column}")]
+async fn get_table_record_file(
+    path: Path<FilePath>,
+    params: Query<FileParams>,
+    state: State,
+) -> Result<Either<HttpResponse, Markup>, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) => send_file(&state, &table, &path, params.inline.is_some()).await,
+        None => Either::Right(not_found(&state).await),
+    })
+}
 
-    match result {
-        Ok(row) => {
-            let mut ui_form = ui::form::Form::from(table.columns.as_slice())
-                .method("post")
-                .action(&format!("/tables/{}/records/{}/edit", table.oid.0, record_id))
-                .row(&row);
+#[get("/tables/{table_oid}/tree/{column}")]
+async fn get_table_tree(
+    path: Path<TreePath>,
+    params: Query<TreeParams>,
+    state: State,
+) -> Result<Markup, WebError> {
+    Ok(match load_table(&state, path.table_oid).await.map_err(ErrorInternalServerError)? {
+        Some(table) => render_tree(&state, &table, &path.column, &params).await,
+        None => not_found(&state).await,
+    })
+}
 
-            if let Some(e) = error {
-                ui_form = ui_form.error(e);
-            }
+#[get("/functions/{function_oid}")]
+async fn get_function(
+    path: Path<FunctionPath>,
+    state: State,
+) -> Result<Markup, WebError> {
+    Ok(match load_function(&state, path.function_oid).await.map_err(ErrorInternalServerError)? {
+        Some(function) => render_function(&state, &function, &[], None, None).await,
+        None => not_found(&state).await,
+    })
+}
 
-            records_page(state, table, html! {
-                (ui_form)
-            }).await
+#[post("/functions/{function_oid}")]
+async fn post_function(
+    path: Path<FunctionPath>,
+    state: State,
+    req: HttpRequest,
+    payload: Payload,
+) -> Result<Markup, WebError> {
+    Ok(match load_function(&state, path.function_oid).await.map_err(ErrorInternalServerError)? {
+        Some(function) => {
+            let values = read_form(&state, &function.arguments, &req, payload).await?;
+
+            call_function(&state, &function, values).await
+        }
+        None => not_found(&state).await,
+    })
+}
+
+#[get("/api/openapi.json")]
+async fn get_api_openapi(state: State) -> Result<HttpResponse, api::Error> {
+    let mut transaction = state.begin().await?;
+    let schemas = db::Schemas::load(&mut transaction, &state.config, state.permissions()).await;
+    let mut tables = Vec::new();
+
+    for table in schemas.iter().flat_map(|s| &s.tables).filter(|t| t.kind != "function") {
+        tables.extend(load_table(&state, table.oid.0).await?);
+    }
+
+    Ok(HttpResponse::Ok().json(api::openapi::document(&tables, &state.config)))
+}
+
+#[get("/api/tables/{table_oid}/records")]
+async fn get_api_table_records(
+    path: Path<RecordsPath>,
+    params: Query<RecordsParams>,
+    query: Query<Vec<(String, String)>>,
+    state: State,
+) -> Result<HttpResponse, api::Error> {
+    let table = load_table(&state, path.table_oid).await?.ok_or_else(api::Error::not_found)?;
+
+    Ok(HttpResponse::Ok().json(api_records(&state, &table, &params, &query).await?))
+}
+
+#[post("/api/tables/{table_oid}/records")]
+async fn post_api_table_records(
+    path: Path<RecordsPath>,
+    record: Json<JsonMap<String, JsonValue>>,
+    state: State,
+) -> Result<HttpResponse, api::Error> {
+    let table = load_table(&state, path.table_oid).await?.ok_or_else(api::Error::not_found)?;
+
+    if !table.insertable {
+        return Err(api::Error::read_only());
+    }
+
+    let record = api_create_record(&state, &table, &record).await?;
+    let mut response = HttpResponse::Created();
+
+    // TODO: Don't rely on `id` field
+    if let Some(id) = record.get("id") {
+        response.insert_header(("Location", format!("/api/tables/{}/records/{}", table.oid.0, id)));
+    }
+
+    Ok(response.json(record))
+}
+
+#[get("/api/tables/{table_oid}/records/{record_id}")]
+async fn get_api_table_record(
+    path: Path<RecordPath>,
+    state: State,
+) -> Result<HttpResponse, api::Error> {
+    let table = load_table(&state, path.table_oid).await?.ok_or_else(api::Error::not_found)?;
+
+    Ok(HttpResponse::Ok().json(api_record(&state, &table, path.record_id).await?))
+}
+
+#[patch("/api/tables/{table_oid}/records/{record_id}")]
+async fn patch_api_table_record(
+    path: Path<RecordPath>,
+    record: Json<JsonMap<String, JsonValue>>,
+    state: State,
+) -> Result<HttpResponse, api::Error> {
+    let table = load_table(&state, path.table_oid).await?.ok_or_else(api::Error::not_found)?;
+
+    if !table.updatable {
+        return Err(api::Error::read_only());
+    }
+
+    Ok(HttpResponse::Ok().json(api_update_record(&state, &table, path.record_id, &record).await?))
+}
+
+#[delete("/api/tables/{table_oid}/records/{record_id}")]
+async fn delete_api_table_record(
+    path: Path<RecordPath>,
+    state: State,
+) -> Result<HttpResponse, api::Error> {
+    let table = load_table(&state, path.table_oid).await?.ok_or_else(api::Error::not_found)?;
+
+    if !table.deletable {
+        return Err(api::Error::read_only());
+    }
+
+    // TODO: Don't rely on `id` field
+    let statement = format!(r#"
+        DELETE FROM "{}"."{}" WHERE id = $1
+        "#,
+        table.schema,
+        table.name,
+    );
+
+    let mut transaction = state.begin().await?;
+
+    let result = sqlx::query(&statement)
+        .bind(path.record_id)
+        .execute(&mut transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    match result.rows_affected() {
+        0 => Err(api::Error::not_found()),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+async fn load_table(
+    state: &State,
+    table_oid: u32,
+) -> Result<Option<db::Table>, sqlx::Error> {
+    let mut transaction = state.begin().await?;
+
+    Ok(db::Table::load(&mut transaction, &state.config, state.permissions(), table_oid).await)
+}
+
+
+/// Functions can do anything the database allows, so a config role either
+/// lets them all be called or none
+async fn load_function(
+    state: &State,
+    function_oid: u32,
+) -> Result<Option<db::Function>, sqlx::Error> {
+    if state.permissions().is_some_and(|role| !role.functions) {
+        return Ok(None);
+    }
+
+    let mut transaction = state.begin().await?;
+
+    Ok(db::Function::load(&mut transaction, &state.config, function_oid).await)
+}
+
+
+/// A page of records as JSON, sorted & filtered the same way as the records list
+async fn api_records(
+    state: &State,
+    table: &db::Table,
+    params: &RecordsParams,
+    query: &[(String, String)],
+) -> Result<api::Page, api::Error> {
+    let per_page = params.per_page.unwrap_or(api::PAGE_SIZE).clamp(1, api::MAX_PAGE_SIZE);
+    let page = params.page.unwrap_or(1).max(1);
+
+    let (conditions, bind_params) = filter_conditions(table, query, 0);
+    let where_clause = where_clause(&conditions);
+
+    let statement = format!(r#"
+        SELECT {} FROM "{}"."{}"
+        {}
+        {}
+        LIMIT {} OFFSET {}
+        "#,
+        table.json_expression(),
+        table.schema,
+        table.name,
+        where_clause,
+        order_clause(table, params),
+        per_page,
+        (page - 1).saturating_mul(per_page),
+    );
+
+    let mut sql_query = sqlx::query(&statement);
+
+    for param in &bind_params {
+        sql_query = sql_query.bind(param);
+    }
+
+    let mut transaction = state.begin().await?;
+
+    let records = sql_query
+        .fetch_all(&mut transaction)
+        .await?
+        .iter()
+        .map(|row| row.try_get(0))
+        .collect::<Result<_, _>>()?;
+
+    let statement = format!(r#"
+        SELECT count(*) FROM "{}"."{}"
+        {}
+        "#,
+        table.schema,
+        table.name,
+        where_clause,
+    );
+
+    let mut count_query = sqlx::query_scalar(&statement);
+
+    for param in bind_params {
+        count_query = count_query.bind(param);
+    }
+
+    let total = count_query.fetch_one(&mut transaction).await?;
+
+    Ok(api::Page { records, total, page, per_page })
+}
+
+
+async fn api_record(
+    state: &State,
+    table: &db::Table,
+    record_id: i64,
+) -> Result<JsonValue, api::Error> {
+    // TODO: Don't rely on `id` field
+    let statement = format!(r#"
+        SELECT {} FROM "{}"."{}"
+        WHERE id = $1
+        "#,
+        table.json_expression(),
+        table.schema,
+        table.name,
+    );
+
+    let mut transaction = state.begin().await?;
+
+    Ok(sqlx::query_scalar(&statement)
+        .bind(record_id)
+        .fetch_one(&mut transaction)
+        .await?)
+}
+
+
+/// Inserts a record the same way as the new record form, except that columns
+/// are only left to their default by leaving them out
+async fn api_create_record(
+    state: &State,
+    table: &db::Table,
+    record: &JsonMap<String, JsonValue>,
+) -> Result<JsonValue, api::Error> {
+    let values = settable(table, api::values(table, record)?, true);
+
+    ui::form::validate(table, &state.config, &values)?;
+
+    let (statement, bind_params) = insertion(table, &values, false);
+    let statement = format!("{statement} RETURNING {}", table.json_expression());
+
+    let mut query = sqlx::query(&statement);
+
+    for param in bind_params {
+        query = bind_value(query, param);
+    }
+
+    let mut transaction = state.begin().await?;
+    let record = query.fetch_one(&mut transaction).await?.try_get(0)?;
+
+    transaction.commit().await?;
+
+    Ok(record)
+}
+
+
+/// Updates only the columns given, the same way as the edit record form
+async fn api_update_record(
+    state: &State,
+    table: &db::Table,
+    record_id: i64,
+    record: &JsonMap<String, JsonValue>,
+) -> Result<JsonValue, api::Error> {
+    let values = settable(table, api::values(table, record)?, false);
+
+    if values.is_empty() {
+        return api_record(state, table, record_id).await;
+    }
+
+    ui::form::validate(table, &state.config, &values)?;
+
+    let (props, bind_params) = assignments(table, &values);
+
+    // TODO: Need to know primary key column, not just assume id
+    let statement = format!(r#"
+        UPDATE "{}"."{}" SET {} WHERE id = ${}
+        RETURNING {}
+        "#,
+        table.schema,
+        table.name,
+        props.join(", "),
+        bind_params.len() + 1,
+        table.json_expression(),
+    );
+
+    let mut query = sqlx::query(&statement);
+
+    for param in bind_params {
+        query = bind_value(query, param);
+    }
+
+    let mut transaction = state.begin().await?;
+
+    // Checked within the same transaction, so that the record can't be moved
+    // in between
+    check_cycles(&mut transaction, table, &[record_id], &values).await?;
+
+    let record = query.bind(record_id).fetch_one(&mut transaction).await?.try_get(0)?;
+
+    transaction.commit().await?;
+
+    Ok(record)
+}
+
+async fn render_records(
+    state: &State,
+    table: &db::Table,
+    params: &RecordsParams,
+    query: &[(String, String)],
+) -> Markup {
+    let columns = table.select_list();
+
+    let (conditions, bind_params) = filter_conditions(table, query, 0);
+    let where_clause = where_clause(&conditions);
+
+    // TODO: Incorporate limit & pagination params
+    let statement = format!(r#"
+        SELECT {} FROM "{}"."{}"
+        {}
+        {}
+        LIMIT 50
+        "#,
+        columns,
+        table.schema,
+        table.name,
+        where_clause,
+        order_clause(table, params),
+    );
+
+    let mut sql_query = sqlx::query(&statement);
+
+    for param in bind_params {
+        sql_query = sql_query.bind(param);
+    }
+
+    let result = async {
+        let mut transaction = state.begin().await?;
+
+        sql_query.fetch_all(&mut transaction).await
+    }.await;
+
+    match result {
+        Ok(rows) => {
+            let actions = table_actions(state, table);
+            let back = format!(
+                "/tables/{}/records?{}",
+                table.oid.0,
+                serde_urlencoded::to_string(query).unwrap(),
+            );
+
+            let ui_facets = ui::table::Facets::new(table, query);
+            let ui_exports = ui::table::Exports::new(table, query);
+            let ui_actions = ui::actions::BulkActions::new(table, actions, query, back);
+            let ui_table = ui::table::Table::new(table, rows).selectable();
+
+            records_page(state, table, html! {
+                (ui_facets)
+                (ui_actions)
+                (ui_table)
+                (ui_exports)
+            }).await
+        }
+        Err(e) => {
+            records_page(state, table, html! {
+                pre {
+                    (statement)
+                }
+                pre {
+                    (format!("{:#?}", e))
+                }
+            }).await
+        }
+    }
+
+}
+
+
+fn order_clause(table: &db::Table, params: &RecordsParams) -> String {
+    let sort_column = params.sort_column.as_ref()
+        .and_then(|c1| table.columns.iter().find(|c2| c1 == &c2.name))
+        // Assumes query returns columns sorted by their ordinal position, in which case
+        // also assume the first column is probably the primary key and should be used
+        // for ordering by default.
+        .or_else(|| table.columns.first())
+        .unwrap();
+
+    let sort_direction = match params.sort_direction.as_deref() {
+        Some("desc") => "desc",
+        _ => "asc",
+    };
+
+    format!("ORDER BY \"{}\"::{} {}", sort_column.name, sort_column.cast_type(), sort_direction)
+}
+
+
+/// Streams the records list as CSV or TSV, sorted & filtered the same way but
+/// without a limit, and with the configured column labels as the header row.
+/// Files don't fit in a spreadsheet, so binary columns are left out.
+async fn export_records(
+    state: &State,
+    table: &db::Table,
+    params: &RecordsParams,
+    query: &[(String, String)],
+) -> Either<HttpResponse, Markup> {
+    let (extension, content_type, delimiter) = match params.format.as_deref() {
+        Some("tsv") => ("tsv", "text/tab-separated-values; charset=utf-8", "E'\\t'"),
+        _ => ("csv", "text/csv; charset=utf-8", "','"),
+    };
+
+    let table_config = state.config.table(&table.schema, &table.name);
+
+    let columns = table.columns.iter()
+        .filter(|c| c.data_type != "bytea")
+        .map(|c| {
+            let label = table_config.and_then(|t| t.label(&c.name)).unwrap_or(&c.name);
+
+            format!("\"{}\" AS \"{}\"", c.name, label.replace('"', "\"\""))
+        })
+        .collect::<Vec<_>>();
+
+    // `COPY` can't take bind params, so filter values are quoted into the statement
+    let conditions = filter_columns(table, query)
+        .map(|(column, value)| filter_condition(column, &db::literal::quote_constant(value)))
+        .collect::<Vec<_>>();
+
+    let statement = format!(r#"
+        COPY (
+            SELECT {} FROM "{}"."{}"
+            {}
+            {}
+        ) TO STDOUT WITH (FORMAT csv, HEADER, DELIMITER {})
+        "#,
+        columns.join(", "),
+        table.schema,
+        table.name,
+        where_clause(&conditions),
+        order_clause(table, params),
+        delimiter,
+    );
+
+    match copy_out(state, statement.clone()).await {
+        Ok(rows) => Either::Left(HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header(("Content-Disposition", format!("attachment; filename=\"{}.{extension}\"", table.name)))
+            .streaming(rows)),
+        Err(e) => Either::Right(records_page(state, table, html! {
+            pre {
+                (statement)
+            }
+            pre {
+                (format!("{:#?}", e))
+            }
+        }).await),
+    }
+}
+
+
+/// Runs a `COPY ... TO STDOUT` in a transaction acting as the user, within a
+/// task of its own so that the rows can still be streamed once the handler
+/// has returned, failing early if the statement itself does
+async fn copy_out(
+    state: &State,
+    statement: String,
+) -> Result<impl Stream<Item = Result<Bytes, sqlx::Error>>, sqlx::Error> {
+    let mut transaction = state.begin().await?;
+    let (started_sender, started) = oneshot::channel();
+    let (rows_sender, rows) = mpsc::channel(16);
+
+    actix_web::rt::spawn(async move {
+        let mut copied = match transaction.copy_out_raw(&statement).await {
+            Ok(copied) => copied,
+            Err(e) => {
+                let _ = started_sender.send(Err(e));
+                return;
+            }
+        };
+
+        let _ = started_sender.send(Ok(()));
+
+        while let Some(row) = copied.next().await {
+            // The download was cancelled
+            if rows_sender.send(row).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    started.await.unwrap_or(Err(sqlx::Error::WorkerCrashed))?;
+
+    Ok(stream::unfold(rows, |mut rows| async move {
+        rows.recv().await.map(|row| (row, rows))
+    }))
+}
+
+
+/// The records list's `filter.<column>` params, where only filters on actual
+/// columns are used, since names are interpolated
+fn filter_columns<'a, 'b>(
+    table: &'a db::Table,
+    query: &'b [(String, String)],
+) -> impl Iterator<Item = (&'a db::Column, &'b String)> {
+    query.iter().filter_map(|(key, value)| {
+        let name = key.strip_prefix(ui::table::FILTER_PREFIX)?;
+
+        table.columns.iter().find(|c| c.name == name).map(|column| (column, value))
+    })
+}
+
+
+/// Compares a column to a filter value as the column's type, so that equal
+/// values match however they're written (eg. `1` and a numeric `1.0`) and
+/// indexes can be used. The `=` operator of a type from an extension is in the
+/// type's own schema, which may not be in the search path, while types without
+/// one (eg. `json`) can only be compared by their canonical text.
+fn filter_condition(column: &db::Column, value: &str) -> String {
+    let element_type = column.element_type.as_deref().unwrap_or(&column.data_type);
+
+    // Enums, arrays, composites & ranges use the polymorphic operators in `pg_catalog`
+    let is_base_type = column.enum_values.is_none() &&
+        column.element_type.is_none() &&
+        column.composite.is_none() &&
+        column.range_subtype.is_none();
+
+    match element_type {
+        "json" | "xml" | "point" | "line" | "lseg" | "box" | "path" | "polygon" | "circle" => {
+            format!("\"{}\"::text = ({}::{})::text", column.name, value, column.cast_type())
+        }
+        _ if is_base_type && column.type_schema != "pg_catalog" => {
+            format!("\"{}\" OPERATOR(\"{}\".=) {}::{}", column.name, column.type_schema, value, column.cast_type())
+        }
+        _ => format!("\"{}\" = {}::{}", column.name, value, column.cast_type()),
+    }
+}
+
+
+/// Conditions for the records list's `filter.<column>` params, along with their
+/// values to bind, numbering placeholders after the first `offset` params
+fn filter_conditions<'a>(
+    table: &db::Table,
+    query: &'a [(String, String)],
+    offset: usize,
+) -> (Vec<String>, Vec<&'a String>) {
+    let mut conditions = Vec::new();
+    let mut bind_params = Vec::new();
+
+    for (column, value) in filter_columns(table, query) {
+        bind_params.push(value);
+        conditions.push(filter_condition(column, &format!("${}", offset + bind_params.len())));
+    }
+
+    (conditions, bind_params)
+}
+
+
+fn where_clause(conditions: &[String]) -> String {
+    match conditions.is_empty() {
+        true => String::new(),
+        false => format!("WHERE {}", conditions.join(" AND ")),
+    }
+}
+
+
+/// The ids of the selected records, locking them for the rest of the transaction
+async fn selected_ids(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    table: &db::Table,
+    selection: &Selection,
+) -> Result<Vec<String>, sqlx::Error> {
+    let (conditions, bind_params) = selection.conditions(table, 0);
+
+    // TODO: Don't rely on `id` field
+    let statement = format!(r#"
+        SELECT id::text FROM "{}"."{}"
+        {}
+        ORDER BY id
+        FOR UPDATE
+        "#,
+        table.schema,
+        table.name,
+        where_clause(&conditions),
+    );
+
+    let mut query = sqlx::query_scalar(&statement);
+
+    for param in bind_params {
+        query = query.bind(param);
+    }
+
+    query.fetch_all(transaction).await
+}
+
+
+async fn count_selected(
+    state: &State,
+    table: &db::Table,
+    selection: &Selection,
+) -> Result<i64, sqlx::Error> {
+    let (conditions, bind_params) = selection.conditions(table, 0);
+
+    let statement = format!(r#"
+        SELECT count(*) FROM "{}"."{}"
+        {}
+        "#,
+        table.schema,
+        table.name,
+        where_clause(&conditions),
+    );
+
+    let mut query = sqlx::query_scalar(&statement);
+
+    for param in bind_params {
+        query = query.bind(param);
+    }
+
+    let mut transaction = state.begin().await?;
+
+    query.fetch_one(&mut transaction).await
+}
+
+
+/// The column chosen to be set on all selected records, where columns that are
+/// always generated (or that the role can't update) can't be set at all
+fn bulk_column<'a>(table: &'a db::Table, query: &[(String, String)]) -> Option<&'a db::Column> {
+    let name = query.iter().find(|(k, _)| k == "column").map(|(_, v)| v)?;
+
+    table.columns.iter().find(|c| &c.name == name && !c.always_generated() && c.updatable)
+}
+
+
+/// Shows how many records are selected, with a choice of column to set on all
+/// of them, and once chosen, that column's input
+async fn render_bulk_edit(
+    state: &State,
+    table: &db::Table,
+    selection: &Selection,
+    column: Option<&db::Column>,
+    rejected: Option<Rejected<'_>>,
+) -> Markup {
+    let count = match count_selected(state, table, selection).await {
+        Ok(count) => count,
+        Err(e) => return records_page(state, table, html! {
+            pre {
+                (format!("{:#?}", e))
+            }
+        }).await,
+    };
+
+    let ui_form = column.filter(|_| count > 0).map(|column| {
+        let mut query = selection.query();
+        query.push(("column", &column.name));
+
+        let mut ui_form = ui::form::Form::from(std::slice::from_ref(column))
+            .method("post")
+            .action(&format!("/tables/{}/bulk/edit?{}", table.oid.0, serde_urlencoded::to_string(query).unwrap()))
+            .explicit()
+            .submit_text(&format!("Update {}", records(count)));
+
+        if let Some(rejected) = rejected {
+            ui_form = ui_form
+                .submitted(rejected.values)
+                .error(rejected.error);
+        }
+
+        ui_form
+    });
+
+    records_page(state, table, html! {
+        c-bulk {
+            p { (records(count)) " selected" }
+            @if count > 0 {
+                form method="get" {
+                    @for (key, value) in selection.query() {
+                        input type="hidden" name=(key) value=(value);
+                    }
+                    label for="bulk-column" { "Set" }
+                    select id="bulk-column" name="column" {
+                        @for c in table.columns.iter().filter(|c| !c.always_generated() && c.updatable) {
+                            option value=(c.name) selected[column.map_or(false, |column| column.name == c.name)] {
+                                (c.name)
+                            }
+                        }
+                    }
+                    button type="submit" { "Choose" }
+                }
+            }
+            a href=(selection.back) { "Cancel" }
+        }
+        @if let Some(ui_form) = ui_form {
+            (ui_form)
+        }
+    }).await
+}
+
+
+async fn render_bulk_delete(
+    state: &State,
+    table: &db::Table,
+    selection: &Selection,
+) -> Markup {
+    let count = match count_selected(state, table, selection).await {
+        Ok(count) => count,
+        Err(e) => return records_page(state, table, html! {
+            pre {
+                (format!("{:#?}", e))
+            }
+        }).await,
+    };
+
+    let action = format!(
+        "/tables/{}/bulk/delete?{}",
+        table.oid.0,
+        serde_urlencoded::to_string(selection.query()).unwrap(),
+    );
+
+    records_page(state, table, html! {
+        c-bulk {
+            @if count > 0 {
+                p { (records(count)) " will be deleted" }
+                form method="post" action=(action) {
+                    button type="submit" { "Delete " (records(count)) }
+                }
+            } @else {
+                p { "No records selected" }
+            }
+            a href=(selection.back) { "Cancel" }
+        }
+    }).await
+}
+
+
+/// Shows the selected records as `INSERT` statements, eg. for fixtures, or
+/// downloads them as a file
+async fn render_bulk_sql(
+    state: &State,
+    table: &db::Table,
+    selection: &Selection,
+    params: &SqlParams,
+) -> Either<HttpResponse, Markup> {
+    let (conditions, bind_params) = selection.conditions(table, 0);
+
+    // Columns generated from others can't be inserted into, and identities are
+    // given explicitly unless left out, so references between records hold up
+    let columns = table.columns.iter()
+        .filter(|c| c.generated.is_none())
+        .filter(|c| c.identity.is_none() || params.omit_identities.is_none())
+        .collect::<Vec<_>>();
+
+    let overriding = match columns.iter().any(|c| c.identity.as_deref() == Some("always")) {
+        true => " OVERRIDING SYSTEM VALUE",
+        false => "",
+    };
+
+    let on_conflict = match params.on_conflict {
+        Some(_) => " ON CONFLICT DO NOTHING",
+        None => "",
+    };
+
+    let target = format!("{}.{}", quote_identifier(&table.schema), quote_identifier(&table.name));
+
+    // Values are quoted by the database, as literals of unknown type that are
+    // cast to each column's type on insert
+    let (prefix, values, suffix) = match columns.is_empty() {
+        true => (format!("INSERT INTO {target} DEFAULT VALUES"), vec!["''".to_owned()], format!("{on_conflict};")),
+        false => (
+            format!(
+                "INSERT INTO {target} ({}){overriding} VALUES (",
+                columns.iter().map(|c| quote_identifier(&c.name)).collect::<Vec<_>>().join(", "),
+            ),
+            columns.iter().map(|c| format!("quote_nullable(\"{}\"::text)", c.name)).collect(),
+            format!("){on_conflict};"),
+        ),
+    };
+
+    let statement = format!(r#"
+        SELECT concat(${}::text, concat_ws(', ', {}), ${}::text)
+        FROM "{}"."{}"
+        {}
+        ORDER BY id
+        "#,
+        bind_params.len() + 1,
+        values.join(", "),
+        bind_params.len() + 2,
+        table.schema,
+        table.name,
+        where_clause(&conditions),
+    );
+
+    let mut query = sqlx::query_scalar(&statement);
+
+    for param in bind_params {
+        query = query.bind(param);
+    }
+
+    query = query.bind(&prefix).bind(&suffix);
+
+    let result = async {
+        let mut transaction = state.begin().await?;
+
+        query.fetch_all(&mut transaction).await
+    }.await;
+
+    let inserts: Vec<String> = match result {
+        Ok(inserts) => inserts,
+        Err(e) => return Either::Right(records_page(state, table, html! {
+            pre {
+                (statement)
+            }
+            pre {
+                (format!("{:#?}", e))
+            }
+        }).await),
+    };
+
+    let sql = inserts.iter().map(|i| format!("{i}\n")).collect::<String>();
+
+    if params.download.is_some() {
+        return Either::Left(HttpResponse::Ok()
+            .content_type("application/sql; charset=utf-8")
+            .insert_header(("Content-Disposition", format!("attachment; filename=\"{}.sql\"", table.name)))
+            .body(sql));
+    }
+
+    let mut download = selection.query();
+    download.extend(params.on_conflict.as_ref().map(|_| ("on_conflict", "true")));
+    download.extend(params.omit_identities.as_ref().map(|_| ("omit_identities", "true")));
+    download.push(("download", "true"));
+
+    Either::Right(records_page(state, table, html! {
+        c-bulk {
+            p { (records(inserts.len() as i64)) " selected" }
+            form method="get" {
+                @for (key, value) in selection.query() {
+                    input type="hidden" name=(key) value=(value);
+                }
+                label {
+                    input type="checkbox" name="on_conflict" value="true" checked[params.on_conflict.is_some()];
+                    "ON CONFLICT DO NOTHING"
+                }
+                label {
+                    input type="checkbox" name="omit_identities" value="true" checked[params.omit_identities.is_some()];
+                    "Leave out identity columns"
+                }
+                button type="submit" { "Update" }
+            }
+            a href=(format!("?{}", serde_urlencoded::to_string(download).unwrap())) download { "Download" }
+            a href=(selection.back) { "Cancel" }
+        }
+        pre.sql { (sql) }
+    }).await)
+}
+
+
+/// Quotes an identifier for statements written out for use elsewhere, whereas
+/// statements run here simply wrap names in quotes
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+
+/// Sets a column to the same value on all selected records, where the records
+/// are checked for cycles one by one, and either all of them are updated or
+/// none are
+async fn bulk_update(
+    state: &State,
+    table: &db::Table,
+    selection: &Selection,
+    column: &db::Column,
+    values: Vec<(String, ui::form::Value)>,
+) -> Either<HttpResponse, Markup> {
+    let values = settable(table, values, false);
+
+    if let Err(error) = ui::form::validate(table, &state.config, &values) {
+        let rejected = Rejected { values: &values, error };
+
+        return Either::Right(render_bulk_edit(state, table, selection, Some(column), Some(rejected)).await);
+    }
+
+    let (assignments, value_params) = assignments(table, &values);
+    let (conditions, condition_params) = selection.conditions(table, value_params.len());
+
+    // Eg. a file input left empty
+    if assignments.is_empty() {
+        let error = ui::form::Error::Invalid(vec![(column.name.clone(), "A value is required".to_owned())]);
+        let rejected = Rejected { values: &values, error };
+
+        return Either::Right(render_bulk_edit(state, table, selection, Some(column), Some(rejected)).await);
+    }
+
+    let statement = format!(r#"
+        UPDATE "{}"."{}" SET {} {}
+        "#,
+        table.schema,
+        table.name,
+        assignments.join(", "),
+        where_clause(&conditions),
+    );
+
+    let result: Result<u64, ui::form::Error> = async {
+        let mut transaction = state.begin().await?;
+        let record_ids = selected_ids(&mut transaction, table, selection).await?
+            .iter()
+            .filter_map(|id| id.parse().ok())
+            .collect::<Vec<i64>>();
+
+        check_cycles(&mut transaction, table, &record_ids, &values).await?;
+
+        let mut query = sqlx::query(&statement);
+
+        for param in value_params {
+            query = bind_value(query, param);
+        }
+
+        for param in condition_params {
+            query = query.bind(param);
+        }
+
+        let updated = query.execute(&mut transaction).await?.rows_affected();
+
+        transaction.commit().await?;
+
+        Ok(updated)
+    }.await;
+
+    match result {
+        Ok(updated) => Either::Left(HttpResponse::SeeOther()
+            .insert_header(("Location", selection.back.as_str()))
+            .cookie(Flash::Info(format!("Updated {}", records(updated as i64))).cookie())
+            .finish()),
+        Err(error) => {
+            let rejected = Rejected { values: &values, error };
+
+            Either::Right(render_bulk_edit(state, table, selection, Some(column), Some(rejected)).await)
+        }
+    }
+}
+
+
+async fn bulk_delete(
+    state: &State,
+    table: &db::Table,
+    selection: &Selection,
+) -> HttpResponse {
+    let (conditions, bind_params) = selection.conditions(table, 0);
+
+    let statement = format!(r#"
+        DELETE FROM "{}"."{}" {}
+        "#,
+        table.schema,
+        table.name,
+        where_clause(&conditions),
+    );
+
+    let result = async {
+        let mut transaction = state.begin().await?;
+        let mut query = sqlx::query(&statement);
+
+        for param in bind_params {
+            query = query.bind(param);
+        }
+
+        let deleted = query.execute(&mut transaction).await?.rows_affected();
+
+        transaction.commit().await?;
+
+        Ok::<_, sqlx::Error>(deleted)
+    }.await;
+
+    let message = match result {
+        Ok(deleted) => Flash::Info(format!("Deleted {}", records(deleted as i64))),
+        Err(e) => Flash::Error(format!(
+            "Deleting failed, so no records were deleted: {}",
+            e.as_database_error().map_or_else(|| e.to_string(), |e| e.message().to_owned()),
+        )),
+    };
+
+    HttpResponse::SeeOther()
+        .insert_header(("Location", selection.back.as_str()))
+        .cookie(message.cookie())
+        .finish()
+}
+
+
+fn records(count: i64) -> String {
+    match count {
+        1 => "1 record".to_owned(),
+        n => format!("{n} records"),
+    }
+}
+
+
+async fn render_tree(
+    state: &State,
+    table: &db::Table,
+    column: &str,
+    params: &TreeParams,
+) -> Markup {
+    let column = table.columns.iter().find(|c| c.name == column);
+
+    let (column, hierarchy) = match column.and_then(|c| Some((c, db::tree::Hierarchy::of(table, c)?))) {
+        Some(found) => found,
+        None => return not_found(state).await,
+    };
+
+    let parent = params.parent.as_deref().unwrap_or_default();
+
+    let result = async {
+        let mut transaction = state.begin().await?;
+
+        let ancestors = match params.partial {
+            Some(_) => Vec::new(),
+            None => hierarchy.ancestors(&mut transaction, table, parent).await?,
+        };
+
+        Ok::<_, sqlx::Error>((ancestors, hierarchy.children(&mut transaction, table, parent).await?))
+    }.await;
+
+    match result {
+        Ok((ancestors, nodes)) => {
+            let ui_tree = ui::tree::Tree::new(table, column, ancestors, nodes);
+
+            match params.partial {
+                Some(_) => ui_tree.nodes(),
+                None => records_page(state, table, html! { (ui_tree) }).await,
+            }
+        }
+        Err(e) => {
+            records_page(state, table, html! {
+                pre {
+                    (format!("{:#?}", e))
+                }
+            }).await
+        }
+    }
+}
+
+
+/// Offers the existing paths of any `ltree` columns as parents for new paths, and
+/// limits columns referencing the table itself to records that wouldn't form a cycle
+async fn suggest_parents<'a>(
+    state: &State,
+    table: &'a db::Table,
+    record_id: Option<i64>,
+    mut ui_form: ui::form::Form<'a>,
+) -> ui::form::Form<'a> {
+    let mut transaction = match state.begin().await {
+        Ok(transaction) => transaction,
+        Err(_) => return ui_form,
+    };
+
+    for column in &table.columns {
+        // Suggestions are only a convenience, so the form is still shown without them
+        match db::tree::Hierarchy::of(table, column) {
+            Some(db::tree::Hierarchy::Path(column)) => {
+                if let Ok(Some(paths)) = db::tree::paths(&mut transaction, table, column).await {
+                    ui_form = ui_form.suggestions(&column.name, paths);
+                }
+            }
+            Some(db::tree::Hierarchy::Parent(column, reference)) => {
+                // With too many to choose from, the parent is entered as is,
+                // still checked for cycles when saved
+                if let Ok(Some(parents)) = db::tree::eligible_parents(&mut transaction, table, column, reference, record_id).await {
+                    ui_form = ui_form.options(&column.name, parents);
+                }
+            }
+            None => {}
+        }
+    }
+
+    ui_form
+}
+
+
+async fn render_new_record(
+    state: &State,
+    table: &db::Table,
+    rejected: Option<Rejected<'_>>,
+) -> Markup {
+    let mut ui_form = ui::form::Form::from(table.columns.as_slice())
+        .method("post")
+        .action(&format!("/tables/{}/records/new", table.oid.0));
+
+    if !table.insertable {
+        ui_form = ui_form.readonly();
+    }
+
+    if let Some(rejected) = rejected {
+        ui_form = ui_form
+            .submitted(rejected.values)
+            .error(rejected.error);
+    }
+
+    let ui_form = suggest_parents(state, table, None, ui_form).await;
+
+    records_page(state, table, html! { (ui_form ) }).await
+}
+
+
+async fn render_edit_record(
+    state: &State,
+    table: &db::Table,
+    record_id: i64, // TODO: Dynamic primary key column, not just "id"
+    rejected: Option<Rejected<'_>>,
+) -> Markup {
+    let columns = table.select_list();
+
+    // TODO: Don't rely on `id` field
+    let statement = format!(r#"
+        SELECT {} FROM "{}"."{}"
+        WHERE id = $1
+        "#,
+        columns,
+        table.schema,
+        table.name,
+    );
+
+    let result = async {
+        let mut transaction = state.begin().await?;
+
+        sqlx::query(&statement)
+            .bind(record_id)
+            .fetch_one(&mut transaction)
+            .await
+    }.await;
+
+    match result {
+        Ok(row) => {
+            let mut ui_form = ui::form::Form::from(table.columns.as_slice())
+                .method("post")
+                .action(&format!("/tables/{}/records/{}/edit", table.oid.0, record_id))
+                .row(&row)
+                .downloads(&format!("/tables/{}/records/{}/files", table.oid.0, record_id));
+
+            if !table.updatable {
+                ui_form = ui_form.readonly();
+            }
+
+            if let Some(rejected) = rejected {
+                ui_form = ui_form
+                    .submitted(rejected.values)
+                    .error(rejected.error);
+            }
+
+            let ui_form = suggest_parents(state, table, Some(record_id), ui_form).await;
+            let ui_actions = ui::actions::RecordActions::new(table, table_actions(state, table), record_id);
+            let ui_export = ui::import::GraphExport::new(table, record_id);
+
+            records_page(state, table, html! {
+                (ui_form)
+                (ui_actions)
+                (ui_export)
+            }).await
         }
         Err(e) => {
             records_page(state, table, html! {
@@ -317,100 +1902,892 @@ async fn render_edit_record(
 }
 
 
-async fn create_new_record(
-    state: &State,
+async fn create_new_record(
+    state: &State,
+    table: &db::Table,
+    values: Vec<(String, ui::form::Value)>,
+) -> Either<HttpResponse, Markup> {
+    let values = settable(table, values, true);
+
+    if let Err(error) = ui::form::validate(table, &state.config, &values) {
+        let rejected = Rejected { values: &values, error };
+
+        return Either::Right(render_new_record(state, table, Some(rejected)).await);
+    }
+
+    let (statement, bind_params) = insertion(table, &values, true);
+
+    let mut query = sqlx::query(&statement);
+
+    for param in bind_params {
+        query = bind_value(query, param);
+    }
+
+    let result = async {
+        let mut transaction = state.begin().await?;
+        let result = query.execute(&mut transaction).await?;
+
+        transaction.commit().await?;
+
+        Ok::<_, sqlx::Error>(result)
+    }.await;
+
+    match result {
+        Ok(_) => Either::Left(HttpResponse::SeeOther()
+            .insert_header(("Location", format!("/tables/{}/records", table.oid.0).as_str()))
+            .finish()
+        ),
+        Err(e) => {
+            let rejected = Rejected { values: &values, error: e.into() };
+
+            Either::Right(render_new_record(state, table, Some(rejected)).await)
+        }
+    }
+}
+
+
+/// Leaves out values for columns that can't be set on new or existing records
+/// respectively, the same way forms don't submit their read-only inputs, as
+/// well as values for columns the table doesn't have, eg. hidden ones
+fn settable(
+    table: &db::Table,
+    mut values: Vec<(String, ui::form::Value)>,
+    new: bool,
+) -> Vec<(String, ui::form::Value)> {
+    values.retain(|(key, _)| table.columns.iter().any(|c| {
+        &c.name == key && match new {
+            true => c.insertable,
+            false => c.updatable,
+        }
+    }));
+
+    values
+}
+
+
+/// An `INSERT` statement for a new record, along with its values to bind, where
+/// forms submit every input, so `omit_empty` leaves empty ones to their default
+fn insertion<'a>(
+    table: &db::Table,
+    values: &'a [(String, ui::form::Value)],
+    omit_empty: bool,
+) -> (String, Vec<&'a ui::form::Value>) {
+    let mut columns = Vec::new();
+    let mut bind_variables = Vec::new();
+    let mut bind_params = Vec::new();
+
+    for (key, value) in values {
+        // Columns left to their default are simply omitted
+        match value {
+            ui::form::Value::Default => continue,
+            ui::form::Value::Text(text) if text.is_empty() && omit_empty => continue,
+            _ => {}
+        }
+
+        // TODO: Optimize this with a map lookup of key -> table column
+        let column = table.columns.iter().find(|c| &c.name == key).unwrap();
+
+        columns.push(format!("\"{}\"", key));
+        bind_variables.push(format!("${}::{}", bind_params.len() + 1, column.cast_type()));
+        bind_params.push(value);
+    }
+
+    let statement = match columns.is_empty() {
+        true => format!(r#"
+            INSERT INTO "{}"."{}" DEFAULT VALUES
+            "#,
+            table.schema,
+            table.name,
+        ),
+        false => format!(r#"
+            INSERT INTO "{}"."{}" ({})
+                VALUES ({})
+            "#,
+            table.schema,
+            table.name,
+            columns.join(", "),
+            bind_variables.join(", "),
+        ),
+    };
+
+    (statement, bind_params)
+}
+
+
+async fn update_record(
+    state: &State,
+    table: &db::Table,
+    record_id: i64,
+    values: Vec<(String, ui::form::Value)>,
+) -> Either<HttpResponse, Markup> {
+    let values = settable(table, values, false);
+
+    if let Err(error) = ui::form::validate(table, &state.config, &values) {
+        let rejected = Rejected { values: &values, error };
+
+        return Either::Right(render_edit_record(state, table, record_id, Some(rejected)).await);
+    }
+
+    let (props, bind_params) = assignments(table, &values);
+
+    // TODO: Need to know primary key column, not just assume id
+    let statement = format!(r#"
+        UPDATE "{}"."{}" SET {} WHERE id = ${}
+        "#,
+        table.schema,
+        table.name,
+        props.join(", "),
+        bind_params.len() + 1,
+    );
+
+    let mut query = sqlx::query(&statement);
+
+    for param in bind_params {
+        query = bind_value(query, param);
+    }
+
+    query = query.bind(record_id);
+
+    // Checked within the same transaction, so that the record can't be moved
+    // in between
+    let result = async {
+        let mut transaction = state.begin().await?;
+
+        check_cycles(&mut transaction, table, &[record_id], &values).await?;
+        query.execute(&mut transaction).await?;
+        transaction.commit().await?;
+
+        Ok::<_, ui::form::Error>(())
+    }.await;
+
+    match result {
+        Ok(_) => Either::Left(HttpResponse::SeeOther()
+            .insert_header(("Location", format!("/tables/{}/records/{}/edit", table.oid.0, record_id).as_str()))
+            .finish()),
+
+        // Reloads the record, but the submitted values are filled back in over it
+        Err(error) => {
+            let rejected = Rejected { values: &values, error };
+
+            Either::Right(render_edit_record(state, table, record_id, Some(rejected)).await)
+        }
+    }
+}
+
+
+/// Assignments for the `SET` clause of an update, along with their values to bind
+fn assignments<'a>(
+    table: &db::Table,
+    values: &'a [(String, ui::form::Value)],
+) -> (Vec<String>, Vec<&'a ui::form::Value>) {
+    let mut props = Vec::new();
+    let mut bind_params = Vec::new();
+
+    for (key, value) in values {
+        let column = table.columns.iter().find(|c| &c.name == key).unwrap();
+
+        match value {
+            ui::form::Value::Default => {
+                props.push(format!("\"{}\" = DEFAULT", column.name));
+            }
+            ui::form::Value::Null => {
+                props.push(format!("\"{}\" = NULL", column.name));
+            }
+            // Other types can't be empty, so emptying their input clears them
+            ui::form::Value::Text(text) if text.is_empty() && column.nullable && !column.is_text() => {
+                props.push(format!("\"{}\" = NULL", column.name));
+            }
+            value => {
+                bind_params.push(value);
+                props.push(format!("\"{}\" = ${}::{}", column.name, bind_params.len(), column.cast_type()));
+            }
+        }
+    }
+
+    (props, bind_params)
+}
+
+
+/// Rejects moving records below themselves (or any of their descendants) by way of
+/// a column referencing the table itself, since it could no longer be reached
+/// from the root of the hierarchy. Runs within the transaction making the
+/// change, which holds the table's hierarchy lock from then on.
+async fn check_cycles(
+    conn: &mut PgConnection,
     table: &db::Table,
-    form_data: &HashMap<String, String>,
-) -> Either<HttpResponse, Markup> {
-    let mut columns = Vec::new();
-    let mut bind_variables = Vec::new();
-    let mut bind_params = Vec::new();
-    let mut position = 1;
+    record_ids: &[i64],
+    values: &[(String, ui::form::Value)],
+) -> Result<(), ui::form::Error> {
+    let mut invalid = Vec::new();
+    let mut locked = false;
 
-    for (key, value) in form_data.iter() {
-        if value.len() == 0 { continue; }
+    for (key, value) in values {
+        let parent = match value {
+            ui::form::Value::Text(text) if !text.is_empty() => text,
+            _ => continue,
+        };
 
-        // TODO: Optimize this with a map lookup of key -> table column
-        let column = table.columns.iter().find(|c| c.name == *key).unwrap();
+        let column = table.columns.iter().find(|c| &c.name == key);
 
-        columns.push(key.to_owned());
-        bind_variables.push(format!("${}::{}", position, column.data_type));
-        bind_params.push(value.to_owned());
+        if let Some(db::tree::Hierarchy::Parent(column, reference)) = column.and_then(|c| db::tree::Hierarchy::of(table, c)) {
+            if !locked {
+                db::tree::lock(&mut *conn, table).await?;
+                locked = true;
+            }
+
+            if db::tree::creates_cycle(&mut *conn, table, column, reference, record_ids, parent).await? {
+                invalid.push((column.name.clone(), "Can't be moved below itself".to_owned()));
+            }
+        }
+    }
+
+    match invalid.is_empty() {
+        true => Ok(()),
+        false => Err(ui::form::Error::Invalid(invalid)),
+    }
+}
+
+
+/// Calls a function with the submitted arguments, showing the results below the
+/// arguments so it can be called again. Arguments left to their default are
+/// omitted, so named notation is used wherever possible. Positional arguments
+/// can't follow named ones, so if any are unnamed, all of them are passed by
+/// position, where only trailing arguments can be omitted, and those left to
+/// their default before others are passed their default expression instead.
+async fn call_function(
+    state: &State,
+    function: &db::Function,
+    values: Vec<(String, ui::form::Value)>,
+) -> Markup {
+    let mut expressions = Vec::new();
+    let mut bind_params = Vec::new();
+
+    for argument in &function.arguments {
+        let value = values.iter().find(|(k, _)| k == &argument.name).map(|(_, v)| v);
+
+        let expression = match value {
+            None | Some(ui::form::Value::Default) => None,
+            Some(ui::form::Value::Null) => Some("NULL".to_owned()),
+            // Other types can't be empty, so emptying their input passes null
+            Some(ui::form::Value::Text(text)) if text.is_empty() && !argument.is_text() => {
+                Some("NULL".to_owned())
+            }
+            Some(value) => {
+                bind_params.push(value);
+                Some(format!("${}::{}", bind_params.len(), argument.cast_type()))
+            }
+        };
 
-        position += 1;
+        expressions.push((argument, expression));
     }
 
+    // Unnamed arguments are named by their position, eg. `$1`
+    let positional = function.arguments.iter().any(|a| a.name.starts_with('$'));
+
+    let arguments = match positional {
+        true => {
+            let passed = expressions.iter().rposition(|(_, e)| e.is_some()).map_or(0, |i| i + 1);
+
+            expressions[..passed].iter()
+                .map(|(argument, expression)| match (expression, &argument.expression) {
+                    (Some(expression), _) => expression.clone(),
+                    (None, Some(default)) => format!("({default})"),
+                    (None, None) => "NULL".to_owned(),
+                })
+                .collect::<Vec<_>>()
+        }
+        false => expressions.iter()
+            .filter_map(|(argument, expression)| Some(format!("\"{}\" => {}", argument.name, expression.as_ref()?)))
+            .collect(),
+    };
+
+    let results = function.results_table();
+
     let statement = format!(r#"
-        INSERT INTO "{}"."{}" ({})
-            VALUES ({})
+        SELECT {} FROM "{}"."{}"({}) AS "{}"
         "#,
-        table.schema,
-        table.name,
-        columns.join(", "),
-        bind_variables.join(", "),
+        results.select_list(),
+        function.schema,
+        function.name,
+        arguments.join(", "),
+        function.name,
     );
 
     let mut query = sqlx::query(&statement);
 
     for param in bind_params {
-        query = query.bind(param);
+        query = bind_value(query, param);
     }
 
-    match query.execute(&state.pool).await {
-        Ok(_) => Either::Left(HttpResponse::SeeOther()
-            .insert_header(("Location", format!("/tables/{}/records", table.oid.0).as_str()))
-            .finish()
-        ),
-        Err(e) => Either::Right(render_new_record(state, table, Some(e)).await)
+    // Functions may well change data, so their transaction is committed
+    let result = async {
+        let mut transaction = state.begin().await?;
+        let rows = query.fetch_all(&mut transaction).await?;
+
+        transaction.commit().await?;
+
+        Ok::<_, sqlx::Error>(rows)
+    }.await;
+
+    match result {
+        Ok(rows) => render_function(state, function, &values, None, Some((&results, rows))).await,
+        Err(e) => render_function(state, function, &values, Some(e.into()), None).await,
     }
 }
 
 
-async fn update_record(
+async fn render_function(
+    state: &State,
+    function: &db::Function,
+    values: &[(String, ui::form::Value)],
+    error: Option<ui::form::Error>,
+    results: Option<(&db::Table, Vec<PgRow>)>,
+) -> Markup {
+    let mut ui_form = ui::form::Form::from(function.arguments.as_slice())
+        .method("post")
+        .action(&format!("/functions/{}", function.oid.0))
+        .submit_text("Run")
+        .submitted(values);
+
+    if let Some(error) = error {
+        ui_form = ui_form.error(error);
+    }
+
+    function_page(state, function, html! {
+        (ui_form)
+        @if let Some((table, rows)) = results {
+            @if table.columns.is_empty() {
+                p { "Done" }
+            } @else if rows.is_empty() {
+                p { "No results" }
+            } @else {
+                (ui::table::Table::new(table, rows).unlinked())
+            }
+        }
+    }).await
+}
+
+
+fn table_actions<'a>(state: &'a State, table: &db::Table) -> &'a [mngr::config::ActionConfig] {
+    // Actions change records, so they're only offered to those who can, where
+    // they could change any column, including those a role hides or can't change
+    if !table.updatable || state.permissions().is_some_and(|role| role.limits_columns(&table.schema, &table.name)) {
+        return &[];
+    }
+
+    state.config.table(&table.schema, &table.name)
+        .and_then(|t| t.actions.as_deref())
+        .unwrap_or_default()
+}
+
+
+/// Runs an action on each of the submitted record ids, all in one transaction
+/// so that either every record or none of them are affected, then goes back to
+/// wherever the action was run from with a message describing the outcome
+async fn run_action(
+    state: &State,
+    table: &db::Table,
+    action: &mngr::config::ActionConfig,
+    form: &[(String, String)],
+) -> HttpResponse {
+    let selection = Selection::new(table, form);
+    let statement = action.sql.replace("$pk", &format!("$1::{}", id_type(table)));
+
+    let result = async {
+        let mut transaction = state.begin().await?;
+        let record_ids = selected_ids(&mut transaction, table, &selection).await?;
+
+        for record_id in &record_ids {
+            sqlx::query(&statement)
+                .bind(record_id)
+                .execute(&mut transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok::<_, sqlx::Error>(record_ids.len())
+    }.await;
+
+    let message = match result {
+        Ok(0) => Flash::Error("No records were selected".to_owned()),
+        Ok(1) => Flash::Info(format!("{}: done", action.name)),
+        Ok(n) => Flash::Info(format!("{}: done for {n} records", action.name)),
+        Err(e) => Flash::Error(format!(
+            "{} failed, so no records were changed: {}",
+            action.name,
+            e.as_database_error().map_or_else(|| e.to_string(), |e| e.message().to_owned()),
+        )),
+    };
+
+    HttpResponse::SeeOther()
+        .insert_header(("Location", selection.back.as_str()))
+        .cookie(message.cookie())
+        .finish()
+}
+
+
+/// Reads the header row of an uploaded file, mapping its fields onto columns
+/// by name where possible, and keeps the file with the session to be run
+async fn upload_import(
+    state: &State,
+    req: &HttpRequest,
+    table: &db::Table,
+    form: &[(String, String)],
+    files: Vec<(String, Vec<u8>)>,
+) -> Markup {
+    let file = files.into_iter().find(|(name, _)| name == "file").map(|(_, bytes)| bytes);
+    let delimiter = form.iter()
+        .find(|(k, _)| k == "delimiter")
+        .and_then(|(_, v)| v.bytes().next())
+        .unwrap_or(b',');
+
+    let result = match file {
+        Some(bytes) if bytes.len() > state.config.uploads.max_size =>
+            Err(format!("Files can be at most {} bytes", state.config.uploads.max_size)),
+        Some(bytes) => String::from_utf8(bytes)
+            .map_err(|_| "The file isn't UTF-8 encoded text".to_owned())
+            .and_then(|data| db::import::Import::parse(data, delimiter).map_err(|e| e.to_string())),
+        None => Err("No file was chosen".to_owned()),
+    };
+
+    let result = result.and_then(|import| {
+        let upload = req.cookie(auth::SESSION_COOKIE)
+            .and_then(|c| state.sessions.keep_upload(c.value(), import.data.clone()))
+            .ok_or_else(|| "Not logged in".to_owned())?;
+
+        Ok((import, upload))
+    });
+
+    match result {
+        Ok((import, upload)) => {
+            let import = import.auto_map(import_columns(state, table).into_iter());
+            let ui_mapping = ui::import::Mapping::new(table, &import, &upload, import_columns(state, table));
+
+            records_page(state, table, html! { (ui_mapping) }).await
+        }
+        Err(error) => {
+            records_page(state, table, html! { (ui::import::Upload::new(table, Some(error))) }).await
+        }
+    }
+}
+
+
+/// Runs an import as mapped, either as a dry run whose outcome is shown along
+/// with the mapping, or for real, returning to the records if every row succeeds
+async fn run_import(
+    state: &State,
+    req: &HttpRequest,
+    table: &db::Table,
+    form: &[(String, String)],
+) -> Either<HttpResponse, Markup> {
+    let upload = ui::import::submitted_upload(form).unwrap_or_default();
+
+    // Uploads are only kept for a while, so they may have to be uploaded again
+    let data = req.cookie(auth::SESSION_COOKIE)
+        .and_then(|c| state.sessions.upload(c.value(), upload))
+        .ok_or_else(|| "The file is no longer available, so it has to be uploaded again".to_owned());
+
+    let import = match data.and_then(|data| ui::import::submitted(form, data).map_err(|e| e.to_string())) {
+        Ok(import) => import,
+        Err(error) => {
+            return Either::Right(records_page(state, table, html! { (ui::import::Upload::new(table, Some(error))) }).await);
+        }
+    };
+
+    let commit = form.iter().any(|(k, v)| k == "commit" && v == "true");
+    let ui_mapping = ui::import::Mapping::new(table, &import, upload, import_columns(state, table));
+
+    // Dry runs roll their own changes back, so there's always something to commit
+    let result = async {
+        let mut transaction = state.begin().await?;
+        let outcome = import.run(&mut transaction, table, commit).await?;
+
+        transaction.commit().await?;
+
+        Ok::<_, db::import::Error>(outcome)
+    }.await;
+
+    let ui_mapping = match result {
+        Ok(outcome) if outcome.committed => {
+            let message = Flash::Info(format!("Imported {}", records(outcome.imported as i64)));
+
+            return Either::Left(HttpResponse::SeeOther()
+                .insert_header(("Location", format!("/tables/{}/records", table.oid.0).as_str()))
+                .cookie(message.cookie())
+                .finish());
+        }
+        Ok(outcome) => ui_mapping.outcome(outcome),
+        Err(db::import::Error::Invalid(error)) => ui_mapping.error(error),
+        Err(db::import::Error::Sql(e)) => ui_mapping.error(format!("{e:#?}")),
+    };
+
+    Either::Right(records_page(state, table, html! { (ui_mapping) }).await)
+}
+
+
+/// Downloads a record along with the records related to it as JSON
+async fn export_graph(
     state: &State,
     table: &db::Table,
     record_id: i64,
-    form_data: &HashMap<String, String>,
+    params: &GraphParams,
 ) -> Either<HttpResponse, Markup> {
-    let keys = form_data.keys()
-        .map(|k| &**k)
-        .collect::<Vec<_>>();
+    let depth = params.depth
+        .unwrap_or(ui::import::DEFAULT_GRAPH_DEPTH)
+        .min(ui::import::MAX_GRAPH_DEPTH);
 
-    let mut props = Vec::new();
+    let result = async {
+        let mut transaction = state.begin().await?;
 
-    for (i, key) in keys.iter().enumerate() {
-        let column = table.columns.iter().find(|c| c.name == *key).unwrap();
+        db::graph::export(&mut transaction, &state.config, state.permissions(), table, &record_id.to_string(), depth).await
+    }.await;
 
-        props.push(format!("{} = ${}::{}", column.name, i + 1, column.data_type));
+    match result {
+        Ok(Some(graph)) => Either::Left(HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header(("Content-Disposition", format!("attachment; filename=\"{}-{record_id}.json\"", table.name)))
+            .body(serde_json::to_string_pretty(&graph).unwrap())),
+        Ok(None) => Either::Right(not_found(state).await),
+        Err(e) => Either::Right(records_page(state, table, html! {
+            pre {
+                (format!("{:#?}", e))
+            }
+        }).await),
     }
+}
 
-    // TODO: Need to know primary key column, not just assume id
+
+/// Inserts the records of an exported graph, going to the new copy of the
+/// record it was exported for
+async fn import_graph(
+    state: &State,
+    table: &db::Table,
+    files: Vec<(String, Vec<u8>)>,
+) -> Either<HttpResponse, Markup> {
+    let file = files.into_iter().find(|(name, _)| name == "file").map(|(_, bytes)| bytes);
+
+    let graph = match file {
+        Some(bytes) if bytes.len() > state.config.uploads.max_size =>
+            Err(format!("Files can be at most {} bytes", state.config.uploads.max_size)),
+        Some(bytes) => serde_json::from_slice::<db::graph::Graph>(&bytes)
+            .map_err(|e| format!("Invalid document: {e}")),
+        None => Err("No file was chosen".to_owned()),
+    };
+
+    let result = match graph {
+        Ok(graph) => async {
+            let mut transaction = state.begin().await?;
+            let imported = db::graph::import(&mut transaction, &state.config, state.permissions(), &graph).await?;
+
+            transaction.commit().await?;
+
+            Ok(imported)
+        }.await
+            .map(|imported| (graph.root, imported))
+            .map_err(|e| match e {
+                db::graph::Error::Invalid(error) => error,
+                db::graph::Error::Sql(e) => format!("{e:#?}"),
+            }),
+        Err(error) => Err(error),
+    };
+
+    match result {
+        Ok((root, imported)) => {
+            let location = match root.schema == table.schema && root.table == table.name {
+                true => format!("/tables/{}/records/{}/edit", table.oid.0, imported.root_id),
+                false => format!("/tables/{}/records", table.oid.0),
+            };
+
+            let message = match imported.existing {
+                0 => format!("Imported {}", records(imported.inserted as i64)),
+                n => format!("Imported {}, using {} that already existed", records(imported.inserted as i64), records(n as i64)),
+            };
+
+            Either::Left(HttpResponse::SeeOther()
+                .insert_header(("Location", location.as_str()))
+                .cookie(Flash::Info(message).cookie())
+                .finish())
+        }
+        Err(error) => {
+            let error = format!("Nothing was imported: {error}");
+
+            Either::Right(records_page(state, table, html! { (ui::import::Upload::new(table, Some(error))) }).await)
+        }
+    }
+}
+
+
+/// Columns a file can be imported into, as (name, label) pairs, ie. all but
+/// generated columns and those the role can't insert into
+fn import_columns<'a>(state: &'a State, table: &'a db::Table) -> Vec<(&'a str, &'a str)> {
+    table.columns.iter()
+        .filter(|c| c.generated.is_none() && c.insertable)
+        .map(|c| (c.name.as_str(), state.config.label(&table.schema, &table.name, &c.name)))
+        .collect()
+}
+
+
+/// Whether a materialized view can be refreshed, which changes its records, so
+/// a config role must have the right to update them
+fn refreshable(state: &State, table: &db::Table) -> bool {
+    table.kind == "materialized view" &&
+    state.permissions().is_none_or(|role| role.rights(&table.schema, &table.name).update)
+}
+
+
+async fn refresh_view(
+    state: &State,
+    table: &db::Table,
+) -> Either<HttpResponse, Markup> {
     let statement = format!(r#"
-        UPDATE "{}"."{}" SET {} WHERE id = ${}
+        REFRESH MATERIALIZED VIEW "{}"."{}"
         "#,
         table.schema,
         table.name,
-        props.join(", "),
-        keys.len() + 1,
     );
 
-    let mut query = sqlx::query(&statement);
+    let result = async {
+        let mut transaction = state.begin().await?;
+        let result = sqlx::query(&statement).execute(&mut transaction).await?;
 
-    for key in keys {
-        query = query.bind(form_data.get(key).unwrap());
-    }
+        transaction.commit().await?;
 
-    query = query.bind(&record_id);
+        Ok::<_, sqlx::Error>(result)
+    }.await;
 
-    match query.execute(&state.pool).await {
+    match result {
         Ok(_) => Either::Left(HttpResponse::SeeOther()
-            .insert_header(("Location", format!("/tables/{}/records/{}/edit", table.oid.0, record_id).as_str()))
+            .insert_header(("Location", format!("/tables/{}/records", table.oid.0).as_str()))
             .finish()),
+        Err(e) => Either::Right(records_page(state, table, html! {
+            pre {
+                (statement)
+            }
+            pre {
+                (format!("{:#?}", e))
+            }
+        }).await),
+    }
+}
+
+
+async fn send_file(
+    state: &State,
+    table: &db::Table,
+    path: &FilePath,
+    inline: bool,
+) -> Either<HttpResponse, Markup> {
+    let column = table.columns.iter().find(|c| c.name == path.column && c.data_type == "bytea");
+
+    let column = match column {
+        Some(column) => column,
+        None => return Either::Right(not_found(state).await),
+    };
+
+    // TODO: Don't rely on `id` field
+    let statement = format!(r#"
+        SELECT "{}" FROM "{}"."{}"
+        WHERE id = $1
+        "#,
+        column.name,
+        table.schema,
+        table.name,
+    );
+
+    let result: Result<Option<Option<Vec<u8>>>, _> = async {
+        let mut transaction = state.begin().await?;
+
+        sqlx::query_scalar(&statement)
+            .bind(path.record_id)
+            .fetch_optional(&mut transaction)
+            .await
+    }.await;
+
+    let bytes = match result {
+        Ok(Some(Some(bytes))) => bytes,
+        _ => return Either::Right(not_found(state).await),
+    };
+
+    let content_type = db::bytea::sniff(&bytes);
+    let is_image = content_type.is_some_and(|c| c.starts_with("image/"));
+
+    let disposition = match inline && is_image {
+        true => "inline".to_owned(),
+        false => format!(
+            "attachment; filename=\"{}-{}-{}.{}\"",
+            table.name,
+            path.record_id,
+            column.name,
+            db::bytea::extension(content_type),
+        ),
+    };
+
+    Either::Left(HttpResponse::Ok()
+        .content_type(content_type.unwrap_or("application/octet-stream"))
+        .insert_header(("Content-Disposition", disposition))
+        .body(bytes))
+}
+
+
+/// Collects submitted values, where forms are only submitted as multipart if they
+/// have file inputs. Uploads are read up to just past the size limit, so that
+/// oversized files can still be reported as such.
+async fn read_form(
+    state: &State,
+    columns: &[db::Column],
+    req: &HttpRequest,
+    payload: Payload,
+) -> Result<Vec<(String, ui::form::Value)>, WebError> {
+    if !req.content_type().starts_with("multipart/") {
+        let form = Form::<Vec<(String, String)>>::from_request(req, &mut payload.into_inner()).await?;
 
-        // FIXME: This shouldn't use same 'edit' function because it reloads the record
-        // and loses all form data
-        Err(e) => Either::Right(render_edit_record(state, table, record_id, Some(e)).await)
+        return Ok(ui::form::values(columns, &form));
     }
+
+    let (form_data, files) = read_multipart(state, req, payload).await?;
+
+    let mut values = ui::form::values(columns, &form_data);
+    values.extend(files.into_iter().map(|(name, bytes)| (name, ui::form::Value::Bytes(bytes))));
+
+    Ok(values)
+}
+
+
+/// Collects a multipart form's fields & files separately, where file inputs
+/// without a file chosen are left out. Malformed or interrupted uploads are
+/// rejected as bad requests rather than read as if they had ended there.
+async fn read_multipart(
+    state: &State,
+    req: &HttpRequest,
+    payload: Payload,
+) -> Result<(Vec<(String, String)>, Vec<(String, Vec<u8>)>), WebError> {
+    let mut multipart = Multipart::new(req.headers(), payload);
+
+    let mut form_data = Vec::new();
+    let mut files = Vec::new();
+
+    while let Some(mut field) = multipart.try_next().await.map_err(ErrorBadRequest)? {
+        let disposition = field.content_disposition();
+        let name = disposition.get_name().unwrap_or_default().to_owned();
+        let is_file = disposition.get_filename().is_some();
+
+        let mut bytes = Vec::new();
+
+        while let Some(chunk) = field.try_next().await.map_err(ErrorBadRequest)? {
+            if bytes.len() <= state.config.uploads.max_size {
+                bytes.extend_from_slice(&chunk);
+            }
+        }
+
+        match is_file {
+            // File inputs without a file chosen submit nothing, keeping the current file
+            true if bytes.is_empty() => {}
+            true => files.push((name, bytes)),
+            false => form_data.push((name, String::from_utf8_lossy(&bytes).into_owned())),
+        }
+    }
+
+    Ok((form_data, files))
+}
+
+
+fn bind_value<'q>(
+    query: sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>,
+    value: &'q ui::form::Value,
+) -> sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments> {
+    match value {
+        ui::form::Value::Bytes(bytes) => query.bind(bytes),
+        ui::form::Value::Text(text) => query.bind(text),
+        // Defaults are written into statements as `DEFAULT` rather than bound
+        ui::form::Value::Default | ui::form::Value::Null => query.bind(None::<String>),
+    }
+}
+
+
+/// A message shown once on the next page, which is passed along as a cookie
+/// that's read (and removed) by `showFlash` in main.js
+enum Flash {
+    Info(String),
+    Error(String),
+}
+
+impl Flash {
+    fn cookie(&self) -> Cookie<'static> {
+        let (level, message) = match self {
+            Flash::Info(message) => ("info", message),
+            Flash::Error(message) => ("error", message),
+        };
+
+        // Percent-encoded, since cookie values can't contain spaces, semicolons, etc.
+        let encoded: String = format!("{level}:{message}").bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b':' => (b as char).to_string(),
+                b => format!("%{b:02X}"),
+            })
+            .collect();
+
+        Cookie::build("flash", encoded).path("/").finish()
+    }
+}
+
+
+// TODO: Don't rely on `id` field
+fn id_type(table: &db::Table) -> String {
+    table.columns.iter()
+        .find(|c| c.name == "id")
+        .map_or("bigint".to_owned(), |c| c.cast_type())
+}
+
+
+/// Turns requests away unless they're logged in, except for logging in itself
+/// and static files, otherwise adding the user to the request's extensions
+fn authorize(req: &ServiceRequest) -> Option<HttpResponse> {
+    let path = req.path();
+
+    if path == "/login" || path.starts_with("/static/") {
+        return None;
+    }
+
+    let state = req.app_data::<Data<State>>().unwrap();
+    let user = req.cookie(auth::SESSION_COOKIE).and_then(|c| state.sessions.user(c.value()));
+
+    match user {
+        Some(user) => {
+            req.extensions_mut().insert(user);
+            None
+        }
+        None if path.starts_with("/api/") => Some(api::Error::new(401, "Not logged in").error_response()),
+        None => {
+            // Only pages can be returned to, rather than submissions
+            let location = match *req.method() == Method::GET {
+                true => format!("/login?{}", serde_urlencoded::to_string([("back", req.uri().to_string())]).unwrap()),
+                false => "/login".to_owned(),
+            };
+
+            Some(HttpResponse::SeeOther().insert_header(("Location", location)).finish())
+        }
+    }
+}
+
+
+/// Where to return to afterwards, as long as it's a local path, so that
+/// links can't be used to redirect elsewhere. Browsers read backslashes as
+/// slashes and skip tabs & newlines, so paths with any of those are refused
+/// (eg. `/\evil.com`).
+fn local_path(back: Option<&str>) -> Option<&str> {
+    back.filter(|back| {
+        back.starts_with('/') &&
+        !back.starts_with("//") &&
+        !back.contains(|c: char| c == '\\' || c.is_ascii_control())
+    })
+}
+
+
+/// Refuses writes to views (etc.) that Postgres doesn't report as writable,
+/// which the UI doesn't offer in the first place
+fn read_only() -> HttpResponse {
+    HttpResponse::MethodNotAllowed().body("This relation is read-only")
 }
 
 
@@ -423,6 +2800,17 @@ async fn not_found(state: &State) -> Markup {
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
+    // Hashes a password read from stdin for a user in the config
+    if env::args().nth(1).as_deref() == Some("hash-password") {
+        let mut password = String::new();
+        io::stdin().read_line(&mut password)?;
+
+        let hash = auth::hash_password(password.trim_end_matches(['\r', '\n'])).map_err(io::Error::other)?;
+        println!("{hash}");
+
+        return Ok(());
+    }
+
     let state = State::new().await;
 
     let app_builder = move || {
@@ -437,15 +2825,56 @@ async fn main() -> io::Result<()> {
                     .add(("Cache-Control", format!("max-age={YEAR_IN_SECONDS}").as_str()))
             );
 
+        // Records may hold files, which are hex encoded in JSON
+        let json_config = JsonConfig::default()
+            .limit(state.config.uploads.max_size * 2 + 64 * 1024)
+            .error_handler(|e, _req| {
+                let response = api::Error::new(400, &e.to_string()).error_response();
+
+                InternalError::from_response(e, response).into()
+            });
+
         App::new()
             .app_data(Data::new(state.clone()))
+            .app_data(json_config)
+            .wrap_fn(|req, srv| match authorize(&req) {
+                None => FutureEither::Left(srv.call(req)),
+                Some(response) => FutureEither::Right(future::ready(Ok(req.into_response(response)))),
+            })
             .service(static_scope)
+            .service(get_index)
+            .service(get_login)
+            .service(post_login)
+            .service(post_logout)
             .service(get_state)
             .service(get_table_records)
+            .service(get_table_records_export)
             .service(get_table_records_new)
             .service(post_table_records_new)
             .service(get_table_record_edit)
             .service(post_table_record_edit)
+            .service(get_table_record_file)
+            .service(post_table_action)
+            .service(get_table_import)
+            .service(post_table_import)
+            .service(post_table_import_run)
+            .service(post_table_import_graph)
+            .service(get_table_record_export)
+            .service(get_table_bulk_edit)
+            .service(post_table_bulk_edit)
+            .service(get_table_bulk_delete)
+            .service(get_table_bulk_sql)
+            .service(post_table_bulk_delete)
+            .service(get_table_tree)
+            .service(post_table_refresh)
+            .service(get_function)
+            .service(post_function)
+            .service(get_api_openapi)
+            .service(get_api_table_records)
+            .service(post_api_table_records)
+            .service(get_api_table_record)
+            .service(patch_api_table_record)
+            .service(delete_api_table_record)
     };
 
     HttpServer::new(app_builder)
@@ -453,3 +2882,34 @@ async fn main() -> io::Result<()> {
         .run()
         .await
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_path_allows_local_paths() {
+        assert_eq!(local_path(Some("/")), Some("/"));
+        assert_eq!(local_path(Some("/tables/1/records?filter.name=a%2Fb")), Some("/tables/1/records?filter.name=a%2Fb"));
+        assert_eq!(local_path(None), None);
+    }
+
+    #[test]
+    fn local_path_refuses_other_sites() {
+        for back in [
+            "",
+            "https://evil.com",
+            "evil.com",
+            "//evil.com",
+            "/\\evil.com",
+            "\\\\evil.com",
+            "/tables\\..\\",
+            "/\t/evil.com",
+            "/\n/evil.com",
+            "javascript:alert(1)",
+        ] {
+            assert_eq!(local_path(Some(back)), None, "{back:?}");
+        }
+    }
+}
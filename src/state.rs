@@ -1,11 +1,26 @@
-use crate::config::Config;
-use sqlx::{Executor, postgres::{PgPool, PgPoolOptions}};
-use std::env;
+use crate::{auth::{Sessions, User}, config::{Config, RoleConfig}};
+use actix_web::{dev::Payload, web::Data, FromRequest, HttpMessage, HttpRequest};
+use sqlx::{Executor, Postgres, Transaction, postgres::{PgPool, PgPoolOptions}};
+use std::{
+    collections::HashMap,
+    env,
+    future::{ready, Ready},
+    sync::{Arc, Mutex},
+};
 
 #[derive(Clone, Debug)]
 pub struct State {
-    pub config: Config,
-    pub pool: PgPool,
+    pub config: Arc<Config>,
+    /// Connections acting as the logged in user's role, if they're mapped to
+    /// one, or else as the user in `DATABASE_URL`
+    pool: PgPool,
+    pub sessions: Sessions,
+    /// The name of the config role limiting the logged in user, if any
+    permissions: Option<String>,
+    /// The logged in user's session variables, as (setting, value) pairs
+    settings: Vec<(String, String)>,
+    /// Pools for each role users are mapped to, created as they're first used
+    role_pools: Arc<Mutex<HashMap<String, PgPool>>>,
 }
 
 impl State {
@@ -15,19 +30,98 @@ impl State {
         // TODO: From an env file or argument
         let database_url = env::var("DATABASE_URL").expect("DATABASE_URL not set");
 
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .after_connect(|conn, _meta| Box::pin(async move {
-                conn.execute("SET application_name = 'alpaca-admin'").await?;
-                Ok(())
-            }))
+        let pool = pool_options(None)
             .connect(&database_url)
             .await
             .unwrap();
 
         State {
-            config,
+            config: Arc::new(config),
             pool,
+            sessions: Sessions::default(),
+            permissions: None,
+            settings: Vec::new(),
+            role_pools: Default::default(),
+        }
+    }
+
+    /// The state for acting as the given user, ie. as their role, where the
+    /// user in `DATABASE_URL` must be a member of it, so that the database
+    /// enforces its privileges and row-level security policies
+    pub fn acting_as(&self, user: Option<&User>) -> Self {
+        let permissions = user.and_then(|u| u.permissions.clone());
+        let settings = user.map(|u| u.settings.clone()).unwrap_or_default();
+
+        let role = match user.and_then(|u| u.role.as_ref()) {
+            Some(role) => role,
+            None => return Self { permissions, settings, ..self.clone() },
+        };
+
+        let pool = self.role_pools.lock().unwrap()
+            .entry(role.clone())
+            .or_insert_with(|| {
+                pool_options(Some(role.clone()))
+                    .connect_lazy_with(self.pool.connect_options().clone())
+            })
+            .clone();
+
+        Self { pool, permissions, settings, ..self.clone() }
+    }
+
+    /// Begins a transaction acting as the logged in user, with their session
+    /// variables set for the transaction alone (`set_config(..., true)`), so
+    /// that users can share connections
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        for (setting, value) in &self.settings {
+            sqlx::query("SELECT set_config($1, $2, true)")
+                .bind(setting)
+                .bind(value)
+                .execute(&mut transaction)
+                .await?;
         }
+
+        Ok(transaction)
+    }
+
+    /// The config role limiting what the logged in user can do, if any
+    pub fn permissions(&self) -> Option<&RoleConfig> {
+        self.config.role(self.permissions.as_deref()?)
     }
 }
+
+/// Handlers take the state acting as the logged in user's role
+impl FromRequest for State {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let state = req.app_data::<Data<State>>().expect("State is missing from app data");
+        let user = req.extensions().get::<User>().cloned();
+
+        ready(Ok(state.acting_as(user.as_ref())))
+    }
+}
+
+/// Connections only ever act as the pool's role, so it's set for the whole
+/// session rather than for each transaction
+fn pool_options(role: Option<String>) -> PgPoolOptions {
+    let set_role = role.map(|r| format!("SET ROLE \"{}\"", r.replace('"', "\"\"")));
+
+    PgPoolOptions::new()
+        .max_connections(5)
+        .after_connect(move |conn, _meta| {
+            let set_role = set_role.clone();
+
+            Box::pin(async move {
+                conn.execute("SET application_name = 'alpaca-admin'").await?;
+
+                if let Some(set_role) = set_role {
+                    conn.execute(set_role.as_str()).await?;
+                }
+
+                Ok(())
+            })
+        })
+}
@@ -0,0 +1,128 @@
+use crate::{config::ActionConfig, db, ui::table::FILTER_PREFIX};
+use maud::{html, Markup, Render};
+
+/// Id of the form that record checkboxes in the records list belong to, since
+/// they're rendered in the table rather than inside the form
+pub const SELECTION_FORM: &str = "selection";
+
+fn action_url(table: &db::Table, index: usize) -> String {
+    format!("/tables/{}/actions/{}", table.oid.0, index)
+}
+
+/// Buttons running each of a table's actions on a single record
+pub struct RecordActions<'a> {
+    table: &'a db::Table,
+    actions: &'a [ActionConfig],
+    record_id: i64,
+}
+
+impl<'a, 'b: 'a> RecordActions<'a> {
+    pub fn new(table: &'b db::Table, actions: &'b [ActionConfig], record_id: i64) -> Self {
+        Self { table, actions, record_id }
+    }
+}
+
+impl<'a> Render for RecordActions<'a> {
+    fn render(&self) -> Markup {
+        let back = format!("/tables/{}/records/{}/edit", self.table.oid.0, self.record_id);
+
+        html! {
+            @if !self.actions.is_empty() {
+                c-actions {
+                    @for (i, action) in self.actions.iter().enumerate() {
+                        form method="post" action=(action_url(self.table, i)) data-confirm=[&action.confirm] {
+                            input type="hidden" name="id" value=(self.record_id);
+                            input type="hidden" name="back" value=(back);
+                            button type="submit" { (action.name) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Buttons for bulk editing, deleting & exporting (as SQL) the records selected in
+/// the records list, and for running each of a table's actions on them, where each button submits
+/// the selection to its own route. Either the checked records are selected, or
+/// all records matching the list's filters, which are passed along for that.
+pub struct BulkActions<'a> {
+    table: &'a db::Table,
+    actions: &'a [ActionConfig],
+    query: &'a [(String, String)],
+    back: String,
+}
+
+impl<'a, 'b: 'a> BulkActions<'a> {
+    pub fn new(
+        table: &'b db::Table,
+        actions: &'b [ActionConfig],
+        query: &'b [(String, String)],
+        back: String,
+    ) -> Self {
+        Self { table, actions, query, back }
+    }
+}
+
+impl<'a> Render for BulkActions<'a> {
+    fn render(&self) -> Markup {
+        let filters = self.query.iter()
+            .filter(|(k, _)| k.starts_with(FILTER_PREFIX))
+            .collect::<Vec<_>>();
+
+        html! {
+            c-actions {
+                form id=(SELECTION_FORM) method="post" {
+                    input type="hidden" name="back" value=(self.back);
+                    @for (key, value) in &filters {
+                        input type="hidden" name=(key) value=(value);
+                    }
+                    label {
+                        input type="checkbox" name="all" value="true";
+                        @if filters.is_empty() {
+                            "All records"
+                        } @else {
+                            "All records matching the filters"
+                        }
+                    }
+                    span { "With selected:" }
+                    @if self.table.updatable {
+                        button
+                            type="submit"
+                            formmethod="get"
+                            formaction=(format!("/tables/{}/bulk/edit", self.table.oid.0))
+                        {
+                            "Edit"
+                        }
+                    }
+                    @if self.table.deletable {
+                        button
+                            type="submit"
+                            formmethod="get"
+                            formaction=(format!("/tables/{}/bulk/delete", self.table.oid.0))
+                        {
+                            "Delete"
+                        }
+                    }
+                    // Exporting records only needs them to be readable
+                    button
+                        type="submit"
+                        formmethod="get"
+                        formaction=(format!("/tables/{}/bulk/sql", self.table.oid.0))
+                    {
+                        "SQL"
+                    }
+                    @for (i, action) in self.actions.iter().enumerate() {
+                        button
+                            type="submit"
+                            formaction=(action_url(self.table, i))
+                            data-confirm=[&action.confirm]
+                        {
+                            (action.name)
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,24 @@
-use crate::db::Column;
+use crate::{config::Config, db::{self, bytea, literal, Check, Column}};
 use maud::{html, Markup, Render};
 use sqlx::{postgres::PgRow, Error as SqlError, Row};
 use time::{macros::format_description, Date, PrimitiveDateTime};
 
+/// Name of the checkbox inputs that opt a column into its default value; the
+/// checkbox value is the column name, so a submission can contain many
+pub const USE_DEFAULT: &str = "_default";
+
+/// Name of the hidden inputs marking array columns, whose elements are then
+/// submitted as `<column>[]`; the marker's value is the column name
+pub const ARRAY: &str = "_array";
+
+/// Name of the hidden inputs marking composite columns, whose attributes are
+/// then submitted as `<column>[<attribute>]`
+pub const COMPOSITE: &str = "_composite";
+
+/// Name of the hidden inputs marking range columns, whose bounds are then
+/// submitted as `<column>[lower]`, `<column>[upper_inclusive]`, etc.
+pub const RANGE: &str = "_range";
+
 #[derive(Copy, Clone, PartialEq)]
 struct Days(usize);
 
@@ -35,18 +51,105 @@ pub struct DateTimeAttributes {
     step: Option<Seconds>,
 }
 
+/// Kept as strings, since decimals need to be rendered exactly
 #[derive(Default, PartialEq)]
 pub struct NumberInputAttributes {
-    min: Option<i64>,
-    max: Option<i64>,
-    step: Option<i64>,
+    min: Option<String>,
+    max: Option<String>,
+    step: Option<String>,
+}
+
+impl NumberInputAttributes {
+    fn bounded(min: &str, max: &str) -> Self {
+        Self {
+            min: Some(min.to_owned()),
+            max: Some(max.to_owned()),
+            step: None,
+        }
+    }
+
+    fn any() -> Self {
+        Self {
+            step: Some("any".to_owned()),
+            ..Self::default()
+        }
+    }
+
+    /// Derives the step & bounds from a `numeric(precision, scale)` column,
+    /// eg. `numeric(5, 2)` allows -999.99 to 999.99 in steps of 0.01
+    fn numeric(precision: Option<i32>, scale: Option<i32>) -> Self {
+        // A negative scale rounds to tens, hundreds, etc.
+        if let (Some(p), Some(s)) = (precision, scale.filter(|s| *s < 0)) {
+            let zeros = "0".repeat(s.unsigned_abs() as usize);
+            let max = format!("{}{zeros}", "9".repeat(p as usize));
+
+            return Self {
+                min: Some(format!("-{max}")),
+                max: Some(max),
+                step: Some(format!("1{zeros}")),
+            };
+        }
+
+        let (precision, scale) = match (precision, scale) {
+            (Some(p), Some(s)) if (0..=p).contains(&s) => (p as usize, s as usize),
+            _ => return Self::any(),
+        };
+
+        let integer = match precision - scale {
+            0 => "0".to_owned(),
+            digits => "9".repeat(digits),
+        };
+        let max = match scale {
+            0 => integer,
+            _ => format!("{integer}.{}", "9".repeat(scale)),
+        };
+        let step = match scale {
+            0 => "1".to_owned(),
+            _ => format!("0.{}1", "0".repeat(scale - 1)),
+        };
+
+        Self {
+            min: Some(format!("-{max}")),
+            max: Some(max),
+            step: Some(step),
+        }
+    }
+}
+
+#[derive(Default, PartialEq)]
+pub struct SelectAttributes {
+    /// Options as (value, label) pairs
+    options: Vec<(String, String)>,
+}
+
+impl SelectAttributes {
+    /// Options labelled by their own values
+    fn values<S: AsRef<str>>(values: &[S]) -> Self {
+        Self {
+            options: values.iter().map(|v| (v.as_ref().to_owned(), v.as_ref().to_owned())).collect(),
+        }
+    }
 }
 
 #[derive(Default, PartialEq)]
 pub struct TextInputAttributes {
+    inputmode: Option<String>,
     minlength: Option<i64>,
     maxlength: Option<i64>,
     placeholder: Option<String>,
+    /// Values offered as the input is typed, without restricting it to them
+    suggestions: Vec<String>,
+}
+
+impl TextInputAttributes {
+    /// Text inputs for types without a dedicated input, where an example
+    /// helps with the expected format
+    fn example(placeholder: &str) -> Self {
+        Self {
+            placeholder: Some(placeholder.to_owned()),
+            ..Self::default()
+        }
+    }
 }
 
 #[derive(Default, PartialEq)]
@@ -58,110 +161,214 @@ pub struct TextAreaAttributes {
 
 #[derive(PartialEq)]
 pub enum InputType {
+    Array(Box<InputType>),
     Boolean,
+    Composite(Vec<Attribute>),
     Date(DateAttributes),
     DateTime(DateTimeAttributes),
+    File,
+    Json,
     Number(NumberInputAttributes),
+    Range(Box<InputType>),
+    Select(SelectAttributes),
     Text(TextInputAttributes),
     TextArea(TextAreaAttributes),
+    Time,
+    Uuid,
 }
 
+/// Attributes of an individual input that don't depend on its type
+struct Input<'a> {
+    id: Option<&'a str>,
+    name: &'a str,
+    class: &'a str,
+    value: Option<&'a str>,
+    placeholder: Option<&'a str>,
+    required: bool,
+    disabled: bool,
+}
 
-pub struct Field<'a> {
-    column: &'a Column,
+/// One attribute of a composite type, with its own input
+#[derive(PartialEq)]
+pub struct Attribute {
+    name: String,
+    data_type: String,
     input_type: InputType,
-    value: Option<String>,
 }
 
-impl<'a> Field<'a> {
-    /*
-    pub fn number(mut self, callback: fn(&mut NumberInputAttributes)) -> Self {
-        let mut attrs = NumberInputAttributes::default();
-        callback(&mut attrs);
-
-        self.input_type = InputType::Number(attrs);
-        self
-    }
-
-    pub fn textarea(mut self, callback: fn(&mut TextAreaAttributes)) -> Self {
-        let mut attrs = TextAreaAttributes::default();
-        callback(&mut attrs);
+impl InputType {
+    /// Chooses the input for a column (or an attribute of a composite column),
+    /// which for composites, arrays and ranges is made up of other inputs
+    fn for_column(column: &Column) -> Self {
+        if !column.attributes.is_empty() {
+            let attributes = column.attributes.iter()
+                .map(|a| Attribute {
+                    name: a.name.clone(),
+                    data_type: a.data_type.clone(),
+                    input_type: InputType::for_column(a),
+                })
+                .collect();
 
-        self.input_type = InputType::TextArea(attrs);
-        self
-    }
-    */
+            return InputType::Composite(attributes);
+        }
 
-    fn value(&mut self, val: String) {
-        self.value = Some(val);
+        match (column.element_type.as_deref(), column.range_subtype.as_deref()) {
+            // Unchecked checkboxes aren't submitted, which would drop elements
+            (Some("bool"), _) =>
+                InputType::Array(Box::new(InputType::Select(SelectAttributes::values(&["t", "f"])))),
+            (Some(element_type), _) =>
+                InputType::Array(Box::new(InputType::new(element_type, column))),
+            (None, Some(subtype)) =>
+                InputType::Range(Box::new(InputType::new(subtype, column))),
+            (None, None) =>
+                InputType::new(&column.data_type, column),
+        }
     }
-}
 
-impl<'a, 'b: 'a> From<&'b Column> for Field<'a> {
-    fn from(column: &'b Column) -> Self {
-        // Unless these are ever individually-configured, this could simply
-        // be moved to render
-        let input_type = match column.data_type.as_ref() {
-            "bool" =>
+    /// Chooses the input for a value of the given type, which is either the
+    /// column's own type or the element type of an array column
+    fn new(data_type: &str, column: &Column) -> Self {
+        match (data_type, &column.enum_values) {
+            (_, Some(options)) =>
+                InputType::Select(SelectAttributes::values(options)),
+            ("bool", _) =>
                 InputType::Boolean,
-            "date" =>
+            ("date", _) =>
                 InputType::Date(DateAttributes::default()),
-            "int4" | "int8" =>
+            ("bytea", _) =>
+                InputType::File,
+            ("cidr", _) =>
+                InputType::Text(TextInputAttributes::example("10.0.0.0/8")),
+            ("float4" | "float8", _) =>
+                InputType::Number(NumberInputAttributes::any()),
+            ("inet", _) =>
+                InputType::Text(TextInputAttributes::example("192.168.0.1/24")),
+            ("int2", _) =>
+                InputType::Number(NumberInputAttributes::bounded("-32768", "32767")),
+            ("int4", _) =>
+                InputType::Number(NumberInputAttributes::bounded("-2147483648", "2147483647")),
+            ("int8", _) =>
                 InputType::Number(NumberInputAttributes::default()),
-            "text" =>
-                InputType::TextArea(TextAreaAttributes::default()),
-            "timestamptz" =>
+            ("interval", _) =>
+                InputType::Text(TextInputAttributes::example("1 day 02:30:00")),
+            ("json" | "jsonb", _) =>
+                InputType::Json,
+            ("ltree", _) =>
+                InputType::Text(TextInputAttributes::example("music.rock.punk")),
+            ("macaddr", _) =>
+                InputType::Text(TextInputAttributes::example("08:00:2b:01:02:03")),
+            ("macaddr8", _) =>
+                InputType::Text(TextInputAttributes::example("08:00:2b:01:02:03:04:05")),
+            ("money", _) =>
+                InputType::Text(TextInputAttributes {
+                    inputmode: Some("decimal".to_owned()),
+                    ..TextInputAttributes::example("1234.56")
+                }),
+            ("numeric", _) =>
+                InputType::Number(NumberInputAttributes::numeric(column.numeric_precision, column.numeric_scale)),
+            ("text", _) =>
+                InputType::TextArea(TextAreaAttributes {
+                    maxlength: column.max_length.map(i64::from),
+                    ..TextAreaAttributes::default()
+                }),
+            ("time", _) =>
+                InputType::Time,
+            ("timestamp" | "timestamptz", _) =>
                 InputType::DateTime(DateTimeAttributes::default()),
+            ("uuid", _) =>
+                InputType::Uuid,
             _ =>
-                InputType::Text(TextInputAttributes::default()),
-        };
-
-        Self {
-            column,
-            input_type,
-            value: None,
+                InputType::Text(TextInputAttributes {
+                    maxlength: column.max_length.map(i64::from),
+                    ..TextInputAttributes::default()
+                }),
         }
     }
-}
 
-impl<'a> Render for Field<'a> {
-    fn render(&self) -> Markup {
-        // TODO: id different from name
-        let id = &self.column.name;
-        let data_type = &self.column.data_type;
-        let required = !self.column.nullable && self.input_type != InputType::Boolean;
+    fn render_input(&self, input: &Input) -> Markup {
+        let Input { id, name, class, value, placeholder, required, disabled } = *input;
 
         html! {
-            label.required[required] for=(id) { (id) }
+            @match self {
+                InputType::Array(element) => {
+                    @match value.map_or(Some(Vec::new()), literal::decode_array) {
+                        Some(elements) => {
+                            @let item_name = format!("{name}[]");
 
-            @match &self.input_type {
+                            // The hidden input makes sure the column is submitted even
+                            // once every element has been removed
+                            fieldset.array id=[id] disabled[disabled] {
+                                input type="hidden" name=(ARRAY) value=(name);
+                                ol {
+                                    @for value in &elements {
+                                        (array_item(element, &item_name, class, value.as_deref(), value.is_none()))
+                                    }
+                                }
+                                template {
+                                    (array_item(element, &item_name, class, None, false))
+                                }
+                                button type="button" data-array-action="add" { "Add" }
+                            }
+                        }
+                        // Literals that can't be decoded are left for the user to edit as-is
+                        None => {
+                            (InputType::Text(TextInputAttributes::default()).render_input(input))
+                        }
+                    }
+                }
                 InputType::Boolean => {
-                    @let checked = self.value.as_deref() == Some("true");
+                    @let checked = value == Some("true");
 
                     input
-                        id=(id)
-                        name=(id)
+                        id=[id]
+                        name=(name)
                         type="checkbox"
-                        class=(data_type)
+                        class=(class)
                         checked[checked]
+                        disabled[disabled]
                     {
                     }
                 }
+                InputType::Composite(attributes) => {
+                    @let fields = value.and_then(literal::decode_row).unwrap_or_default();
+
+                    fieldset.composite id=[id] disabled[disabled] {
+                        input type="hidden" name=(COMPOSITE) value=(name);
+                        @for (i, attribute) in attributes.iter().enumerate() {
+                            @let attribute_name = format!("{name}[{}]", attribute.name);
+                            @let input = Input {
+                                id: None,
+                                name: &attribute_name,
+                                class: &attribute.data_type,
+                                value: fields.get(i).and_then(|f| f.as_deref()),
+                                placeholder: None,
+                                required: false,
+                                disabled: false,
+                            };
+
+                            label {
+                                span { (attribute.name) }
+                                (attribute.input_type.render_input(&input))
+                            }
+                        }
+                    }
+                }
                 InputType::Date(attrs) => {
                     @let format = format_description!("[year]-[month]-[day]");
                     @let min = attrs.min.map(|min| min.format(&format).unwrap());
                     @let max = attrs.max.map(|max| max.format(&format).unwrap());
 
                     input
-                        id=(id)
-                        name=(id)
+                        id=[id]
+                        name=(name)
                         type="date"
-                        class=(data_type)
+                        class=(class)
                         min=[min]
                         max=[max]
                         step=[attrs.step]
-                        value=[&self.value]
+                        value=[value]
                         required[required]
+                        disabled[disabled]
                     {
                     }
                 }
@@ -174,78 +381,407 @@ impl<'a> Render for Field<'a> {
                     //
                     // TODO: And it doesn't always work because it can still include milliseconds,
                     // which will not populate the input
-                    @let value = self.value.as_ref().map(|v| v.split("+").next().unwrap().to_owned());
+                    @let value = value.map(|v| v.split('+').next().unwrap().to_owned());
 
                     input
-                        id=(id)
-                        name=(id)
+                        id=[id]
+                        name=(name)
                         type="datetime-local"
-                        class=(data_type)
+                        class=(class)
                         min=[min]
                         max=[max]
                         step=[attrs.step]
                         value=[value]
                         required[required]
+                        disabled[disabled]
+                    {
+                    }
+                }
+                InputType::File => {
+                    // Existing files are kept unless a new one is chosen, so only
+                    // require one if there isn't one already
+                    input
+                        id=[id]
+                        name=(name)
+                        type="file"
+                        class=(class)
+                        required[required && value.is_none()]
+                        disabled[disabled]
+                    {
+                    }
+                }
+                InputType::Json => {
+                    // Documents are edited pretty-printed, unless they don't parse
+                    // (ie. they were submitted that way) and are shown as-is
+                    @let value = value.map(|v| match serde_json::from_str::<serde_json::Value>(v) {
+                        Ok(document) => serde_json::to_string_pretty(&document).unwrap(),
+                        Err(_) => v.to_owned(),
+                    });
+                    @let rows = value.as_ref().map_or(1, |v| v.lines().count()).clamp(3, 20);
+
+                    textarea
+                        id=[id]
+                        name=(name)
+                        class=(class)
+                        rows=(rows)
+                        spellcheck="false"
+                        placeholder=[placeholder]
+                        required[required]
+                        disabled[disabled]
                     {
+                        @if let Some(value) = value {
+                            (value)
+                        }
                     }
                 }
                 InputType::Number(attrs) => {
                     input
-                        id=(id)
-                        name=(id)
+                        id=[id]
+                        name=(name)
                         type="number"
-                        class=(data_type)
-                        min=[attrs.min]
-                        max=[attrs.max]
-                        step=[attrs.step]
-                        value=[&self.value]
+                        class=(class)
+                        min=[&attrs.min]
+                        max=[&attrs.max]
+                        step=[&attrs.step]
+                        placeholder=[placeholder]
+                        value=[value]
+                        required[required]
+                        disabled[disabled]
+                    {
+                    }
+                }
+                InputType::Range(bound) => {
+                    // Empty ranges & null have no bounds, so they're chosen separately
+                    @let kind = match value {
+                        Some(value) if value.eq_ignore_ascii_case("empty") => "empty",
+                        Some("") | None if !required => "null",
+                        _ => "bounds",
+                    };
+                    @let range = value.and_then(literal::decode_range).unwrap_or(literal::Range {
+                        lower_inclusive: true,
+                        ..literal::Range::default()
+                    });
+                    @let bound_input = |part: &str, value: Option<&str>| bound.render_input(&Input {
+                        id: None,
+                        name: &format!("{name}[{part}]"),
+                        class,
+                        value,
+                        placeholder: Some("Unbounded"),
+                        required: false,
+                        disabled: false,
+                    });
+
+                    fieldset.range id=[id] disabled[disabled] {
+                        input type="hidden" name=(RANGE) value=(name);
+                        select name=(format!("{name}[kind]")) title="Kind of range" {
+                            option value="bounds" selected[kind == "bounds"] { "Range" }
+                            option value="empty" selected[kind == "empty"] { "Empty" }
+                            @if !required {
+                                option value="null" selected[kind == "null"] { "None" }
+                            }
+                        }
+                        select name=(format!("{name}[lower_inclusive]")) title="Lower bound" {
+                            option value="true" selected[range.lower_inclusive] { "[" }
+                            option value="false" selected[!range.lower_inclusive] { "(" }
+                        }
+                        (bound_input("lower", range.lower.as_deref()))
+                        span { "to" }
+                        (bound_input("upper", range.upper.as_deref()))
+                        select name=(format!("{name}[upper_inclusive]")) title="Upper bound" {
+                            option value="true" selected[range.upper_inclusive] { "]" }
+                            option value="false" selected[!range.upper_inclusive] { ")" }
+                        }
+                    }
+                }
+                InputType::Select(attrs) => {
+                    select
+                        id=[id]
+                        name=(name)
+                        class=(class)
                         required[required]
+                        disabled[disabled]
                     {
+                        @if !required || value.is_none() {
+                            option value="" {}
+                        }
+                        @for (option, label) in &attrs.options {
+                            @let selected = value == Some(option.as_str());
+
+                            option value=(option) selected[selected] { (label) }
+                        }
                     }
                 }
                 InputType::Text(attrs) => {
+                    @let list = match attrs.suggestions.is_empty() {
+                        true => None,
+                        false => Some(format!("{name}-suggestions")),
+                    };
+
                     input
-                        id=(id)
-                        name=(id)
+                        id=[id]
+                        name=(name)
                         type="text"
-                        class=(data_type)
+                        class=(class)
+                        list=[&list]
+                        inputmode=[&attrs.inputmode]
                         minlength=[attrs.minlength]
                         maxlength=[attrs.maxlength]
-                        placeholder=[&attrs.placeholder]
-                        value=[&self.value]
+                        placeholder=[attrs.placeholder.as_deref().or(placeholder)]
+                        value=[value]
                         required[required]
+                        disabled[disabled]
                     {
                     }
+                    @if let Some(list) = &list {
+                        datalist id=(list) {
+                            @for suggestion in &attrs.suggestions {
+                                option value=(suggestion) {}
+                            }
+                        }
+                    }
                 }
                 InputType::TextArea(attrs) => {
                     @let rows = attrs.rows.unwrap_or(1);
 
                     textarea
-                        id=(id)
-                        name=(id)
-                        class=(data_type)
+                        id=[id]
+                        name=(name)
+                        class=(class)
                         maxlength=[attrs.maxlength]
                         minlength=[attrs.minlength]
                         rows=(rows)
+                        placeholder=[placeholder]
                         required[required]
+                        disabled[disabled]
                     {
-                        @if let Some(value) = &self.value {
+                        @if let Some(value) = value {
                             (value)
                         }
                     }
                 }
+                InputType::Time => {
+                    input
+                        id=[id]
+                        name=(name)
+                        type="time"
+                        class=(class)
+                        step="1"
+                        value=[value]
+                        required[required]
+                        disabled[disabled]
+                    {
+                    }
+                }
+                InputType::Uuid => {
+                    input
+                        id=[id]
+                        name=(name)
+                        type="text"
+                        class=(class)
+                        pattern="[0-9a-fA-F]{8}-?([0-9a-fA-F]{4}-?){3}[0-9a-fA-F]{12}"
+                        spellcheck="false"
+                        placeholder=[placeholder]
+                        value=[value]
+                        required[required]
+                        disabled[disabled]
+                    {
+                    }
+                    button type="button" data-generate-uuid disabled[disabled] { "Generate" }
+                }
+            }
+        }
+    }
+}
+
+/// A single, movable & removable element of an array input
+/// An array element's input, along with a checkbox making it null, which is
+/// submitted right after the input (as `<name>null`) so the two can be paired
+fn array_item(element: &InputType, name: &str, class: &str, value: Option<&str>, null: bool) -> Markup {
+    let input = Input {
+        id: None,
+        name,
+        class,
+        value,
+        placeholder: None,
+        required: false,
+        disabled: false,
+    };
+
+    html! {
+        li {
+            (element.render_input(&input))
+            label {
+                input type="checkbox" name=(format!("{name}null")) value="true" checked[null];
+                "NULL"
             }
+            button type="button" data-array-action="up" title="Move up" { "↑" }
+            button type="button" data-array-action="down" title="Move down" { "↓" }
+            button type="button" data-array-action="remove" title="Remove" { "✕" }
         }
     }
 }
 
 
+pub struct Field<'a> {
+    column: &'a Column,
+    download: Option<String>,
+    errors: Vec<String>,
+    input_type: InputType,
+    readonly: bool,
+    use_default: bool,
+    value: Option<String>,
+}
+
+impl<'a> Field<'a> {
+    /*
+    pub fn number(mut self, callback: fn(&mut NumberInputAttributes)) -> Self {
+        let mut attrs = NumberInputAttributes::default();
+        callback(&mut attrs);
+
+        self.input_type = InputType::Number(attrs);
+        self
+    }
+
+    pub fn textarea(mut self, callback: fn(&mut TextAreaAttributes)) -> Self {
+        let mut attrs = TextAreaAttributes::default();
+        callback(&mut attrs);
+
+        self.input_type = InputType::TextArea(attrs);
+        self
+    }
+    */
+
+    fn value(&mut self, val: String) {
+        self.value = Some(val);
+    }
+
+    /// Describes what the database will do if no value is given, which
+    /// for generated columns is the only thing that can happen
+    fn placeholder(&self) -> Option<String> {
+        let column = &self.column;
+
+        match (column.identity.as_deref(), column.generated.as_deref()) {
+            (Some(identity), _) =>
+                Some(format!("Generated {identity} as identity")),
+            (_, Some(generated)) =>
+                Some(format!("Generated always as ({}) {generated}", column.expression.as_deref().unwrap_or_default())),
+            _ =>
+                column.default_expression().map(|expr| expr.to_owned()),
+        }
+    }
+}
+
+impl<'a, 'b: 'a> From<&'b Column> for Field<'a> {
+    fn from(column: &'b Column) -> Self {
+        // Unless these are ever individually-configured, this could simply
+        // be moved to render
+        let input_type = InputType::for_column(column);
+
+        Self {
+            column,
+            download: None,
+            errors: Vec::new(),
+            input_type,
+            readonly: column.always_generated() || !column.insertable,
+            use_default: column.has_default() && !column.always_generated() && column.insertable,
+            value: None,
+        }
+    }
+}
+
+impl<'a> Render for Field<'a> {
+    fn render(&self) -> Markup {
+        // TODO: id different from name
+        let id = &self.column.name;
+        let required = !self.column.nullable && self.input_type != InputType::Boolean;
+        let placeholder = self.placeholder();
+
+        let input = Input {
+            id: Some(id),
+            name: id,
+            class: &self.column.data_type,
+            value: self.value.as_deref(),
+            placeholder: placeholder.as_deref(),
+            required,
+            disabled: self.readonly || self.use_default,
+        };
+
+        html! {
+            label.required[required] for=(id) { (id) }
+
+            (self.input_type.render_input(&input))
+
+            @if let (Some(url), Some(summary)) = (&self.download, self.value.as_deref().and_then(bytea::Summary::parse)) {
+                small.file {
+                    @if summary.is_image() {
+                        img src=(format!("{url}?inline")) alt=(id);
+                    }
+                    a href=(url) { "Download" } " (" (summary.size) " bytes)"
+                }
+            }
+
+            @for error in &self.errors {
+                small.error { (error) }
+            }
+
+            @if let Some(domain) = &self.column.domain {
+                small.domain {
+                    "Domain " code { (domain) }
+                    @for check in &self.column.checks {
+                        br;
+                        code { (check.definition) }
+                    }
+                }
+            }
+
+            @if self.readonly {
+                @if let Some(placeholder) = &placeholder {
+                    small { (placeholder) }
+                }
+            } @else if self.column.has_default() {
+                label.default {
+                    input
+                        type="checkbox"
+                        name=(USE_DEFAULT)
+                        value=(id)
+                        data-default-for=(id)
+                        checked[self.use_default]
+                    {
+                    }
+                    "Use default"
+                    @if let Some(placeholder) = &placeholder {
+                        ": " code { (placeholder) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+/// Why a submission was rejected
+#[derive(Debug)]
+pub enum Error {
+    /// The database rejected the statement
+    Sql(SqlError),
+    /// Values were rejected before reaching the database, as (column, message) pairs
+    Invalid(Vec<(String, String)>),
+}
+
+impl From<SqlError> for Error {
+    fn from(error: SqlError) -> Self {
+        Error::Sql(error)
+    }
+}
+
+
 #[derive(Default)]
 pub struct Form<'a> {
     action: Option<String>,
-    error: Option<SqlError>,
+    error: Option<Error>,
     fields: Vec<Field<'a>>,
     method: Option<String>,
+    readonly: bool,
     submit_text: Option<String>,
 }
 
@@ -260,24 +796,146 @@ impl<'a> Form<'a> {
         self
     }
 
-    pub fn error(mut self, error: SqlError) -> Self {
+    pub fn error(mut self, error: impl Into<Error>) -> Self {
+        let error = error.into();
+
+        if let Error::Invalid(invalid) = &error {
+            for (column, message) in invalid {
+                if let Some(field) = self.fields.iter_mut().find(|f| &f.column.name == column) {
+                    field.errors.push(message.to_owned());
+                }
+            }
+        }
+
         self.error = Some(error);
         self
     }
 
+    /// Links existing files to the route serving them, as `<url>/<column>`
+    pub fn downloads(mut self, url: &str) -> Self {
+        for field in &mut self.fields {
+            if field.input_type == InputType::File {
+                field.download = Some(format!("{url}/{}", field.column.name));
+            }
+        }
+
+        self
+    }
+
+    /// Limits a column to a choice of values, as (value, label) pairs, eg. the
+    /// records a foreign key can reference
+    pub fn options(mut self, column: &str, options: Vec<(String, String)>) -> Self {
+        if let Some(field) = self.fields.iter_mut().find(|f| f.column.name == column) {
+            field.input_type = InputType::Select(SelectAttributes { options });
+        }
+
+        self
+    }
+
+    /// Offers values for a column's text input, eg. existing paths of an `ltree`
+    pub fn suggestions(mut self, column: &str, suggestions: Vec<String>) -> Self {
+        let field = self.fields.iter_mut().find(|f| f.column.name == column);
+
+        if let Some(Field { input_type: InputType::Text(attrs), .. }) = field {
+            attrs.suggestions = suggestions;
+        }
+
+        self
+    }
+
+    /// Fills the form back in with submitted values, eg. after they were rejected
+    pub fn submitted(mut self, values: &[(String, Value)]) -> Self {
+        for (column, value) in values {
+            if let Some(field) = self.fields.iter_mut().find(|f| &f.column.name == column) {
+                match value {
+                    Value::Default => {
+                        field.use_default = true;
+                    }
+                    Value::Text(text) => {
+                        field.value(text.to_owned());
+                        field.use_default = false;
+                    }
+                    Value::Null => {
+                        field.use_default = false;
+                    }
+                    // File inputs can't be filled back in
+                    Value::Bytes(_) => {}
+                }
+            }
+        }
+
+        self
+    }
+
     pub fn row(mut self, row: &PgRow) -> Self {
         for field in &mut self.fields {
-            let value: String = row.try_get(field.column.name.as_str()).unwrap();
+            let value: Option<String> = row.try_get(field.column.name.as_str()).unwrap();
+
+            if let Some(value) = value {
+                field.value(value);
+            }
+
+            // Existing values are never replaced by the database, so opting into
+            // the default is left to the user, and generated values (or those
+            // the role can't update) are shown but never submitted
+            field.use_default = false;
+            field.readonly = field.column.is_generated() || !field.column.updatable;
+        }
+
+        self
+    }
+
+    /// Leaves opting into defaults to the user, eg. when setting a value on
+    /// existing records, where the default isn't what happens otherwise
+    pub fn explicit(mut self) -> Self {
+        for field in &mut self.fields {
+            field.use_default = false;
+        }
 
-            field.value(value);
+        self
+    }
+
+    pub fn submit_text(mut self, text: &str) -> Self {
+        self.submit_text = Some(text.to_owned());
+        self
+    }
+
+    /// Shows the values without allowing them to be changed, eg. for records of
+    /// views that can't be updated
+    pub fn readonly(mut self) -> Self {
+        for field in &mut self.fields {
+            field.readonly = true;
+            field.use_default = false;
         }
 
+        self.readonly = true;
         self
     }
 
     fn add_field(&mut self, field: Field<'a>) {
         self.fields.push(field);
     }
+
+    /// Finds the domain check constraint the error is about, along with the
+    /// column it's from when only one submitted field carries it, since errors
+    /// name the constraint & domain but not the offending column
+    fn violated_check(&self, error: &SqlError) -> Option<(Option<&str>, &Check)> {
+        let constraint = error.as_database_error()?.constraint()?;
+        let mut violating = self.fields.iter()
+            .filter(|field| !field.readonly)
+            .filter_map(|field| {
+                field.column.checks.iter()
+                    .find(|check| check.name == constraint)
+                    .map(|check| (field.column.name.as_str(), check))
+            });
+
+        let (column, check) = violating.next()?;
+
+        match violating.next() {
+            None => Some((Some(column), check)),
+            Some(_) => Some((None, check)),
+        }
+    }
 }
 
 impl<'a, 'b: 'a> From<&'b [Column]> for Form<'a> {
@@ -285,8 +943,6 @@ impl<'a, 'b: 'a> From<&'b [Column]> for Form<'a> {
         let mut form = Self::default();
 
         for column in columns.iter() {
-            if column.always_generated() { continue; }
-
             form.add_field(Field::from(column));
         }
 
@@ -297,23 +953,214 @@ impl<'a, 'b: 'a> From<&'b [Column]> for Form<'a> {
 impl<'a> Render for Form<'a> {
     fn render(&self) -> Markup {
         let submit_text = self.submit_text.as_deref().unwrap_or("Submit");
+        let enctype = self.fields.iter()
+            .any(|f| f.input_type == InputType::File)
+            .then_some("multipart/form-data");
 
         html! {
             c-form {
-                form method=[&self.method] action=[&self.action] {
+                form method=[&self.method] action=[&self.action] enctype=[enctype] {
                     @for field in &self.fields {
                         c-form-field { (field) }
                     }
-                    c-form-controls {
-                        button type="submit" { (submit_text) }
+                    @if !self.readonly {
+                        c-form-controls {
+                            button type="submit" { (submit_text) }
+                        }
                     }
-                    @if let Some(error) = &self.error {
-                        output class="error" {
-                            pre { (format!("{error:#?}")) }
+                    @match &self.error {
+                        Some(Error::Sql(error)) => {
+                            output class="error" {
+                                @match self.violated_check(error) {
+                                    Some((Some(column), check)) => p {
+                                        code { (column) } " must satisfy " code { (check.definition) }
+                                    },
+                                    Some((None, check)) => p {
+                                        "A value must satisfy " code { (check.definition) }
+                                    },
+                                    None => {}
+                                }
+                                pre { (format!("{error:#?}")) }
+                            }
+                        }
+                        Some(Error::Invalid(_)) => {
+                            output class="error" {
+                                p { "Some values are invalid, see above" }
+                            }
                         }
+                        None => {}
                     }
                 }
             }
         }
     }
 }
+
+
+/// A single column's value as submitted through a `Form` (or the JSON API)
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    /// The user opted into the column's default
+    Default,
+    /// Explicitly no value, which forms can't submit, since empty inputs are
+    /// only null for types that can't be empty
+    Null,
+    Text(String),
+    /// An uploaded file
+    Bytes(Vec<u8>),
+}
+
+/// Pairs submitted column names with their values, where opting into a
+/// default takes precedence over anything the input itself submitted, and
+/// the parts of composites, arrays and ranges are encoded into a single literal
+pub fn values(columns: &[Column], form_data: &[(String, String)]) -> Vec<(String, Value)> {
+    let mut values = Vec::new();
+
+    for column in columns {
+        let name = &column.name;
+
+        if form_data.iter().any(|(k, v)| k == USE_DEFAULT && v == name) {
+            values.push((name.to_owned(), Value::Default));
+        } else if let Some(text) = submitted(column, name, form_data) {
+            values.push((name.to_owned(), Value::Text(text)));
+        }
+    }
+
+    values
+}
+
+/// The value submitted for a column (or a composite's attribute) under the
+/// given input name, if any, with leaving every part of a composite, array
+/// or range input empty meaning no value at all, like any other input
+fn submitted(column: &Column, name: &str, form_data: &[(String, String)]) -> Option<String> {
+    let marked = |marker: &str| form_data.iter().any(|(k, v)| k == marker && v == name);
+    let part = |part: &str| {
+        let key = format!("{name}[{part}]");
+
+        form_data.iter()
+            .find(|(k, v)| k == &key && !v.is_empty())
+            .map(|(_, v)| v.to_owned())
+    };
+
+    if !column.attributes.is_empty() && marked(COMPOSITE) {
+        let fields = column.attributes.iter()
+            .map(|a| submitted(a, &format!("{name}[{}]", a.name), form_data).filter(|v| !v.is_empty()))
+            .collect::<Vec<_>>();
+
+        if fields.iter().all(Option::is_none) {
+            return Some(String::new());
+        }
+
+        let fields = fields.iter().map(Option::as_deref).collect::<Vec<_>>();
+
+        return Some(literal::encode_row(&fields));
+    }
+
+    if let (Some(element_type), true) = (&column.element_type, marked(ARRAY)) {
+        let key = format!("{name}[]");
+        let null_key = format!("{name}[]null");
+        let is_text = matches!(element_type.as_str(), "text" | "varchar" | "bpchar" | "citext");
+        let mut elements = Vec::new();
+
+        // Each element's null checkbox follows its input, so it applies to the
+        // last one; other types can't be empty, so emptying their input is null
+        for (k, v) in form_data {
+            if k == &key {
+                elements.push((!v.is_empty() || is_text).then_some(v.as_str()));
+            } else if k == &null_key {
+                if let Some(element) = elements.last_mut() {
+                    *element = None;
+                }
+            }
+        }
+
+        // Like other inputs, removing every element is no value at all if it can be null
+        if elements.is_empty() && column.nullable {
+            return Some(String::new());
+        }
+
+        return Some(literal::encode_array(&elements));
+    }
+
+    if column.range_subtype.is_some() && marked(RANGE) {
+        let range = literal::Range {
+            lower: part("lower"),
+            upper: part("upper"),
+            lower_inclusive: part("lower_inclusive").as_deref() == Some("true"),
+            upper_inclusive: part("upper_inclusive").as_deref() == Some("true"),
+        };
+
+        // Bounds left empty are unbounded, eg. `(,)`, unlike a range that's empty
+        // or none at all (null); without a kind, it's none if it has no bounds
+        return match (part("kind").as_deref(), &range.lower, &range.upper) {
+            (Some("empty"), _, _) => Some("empty".to_owned()),
+            (Some("null"), _, _) | (None, None, None) => Some(String::new()),
+            _ => Some(literal::encode_range(&range)),
+        };
+    }
+
+    // Also covers literals that couldn't be decoded, which are edited as text
+    form_data.iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.to_owned())
+}
+
+/// Checks submitted values that can be checked before reaching the database,
+/// ie. that uploads aren't too large and JSON documents parse and match any
+/// schema configured for them
+pub fn validate(
+    table: &db::Table,
+    config: &Config,
+    values: &[(String, Value)],
+) -> Result<(), Error> {
+    let table_config = config.table(&table.schema, &table.name);
+    let mut invalid = Vec::new();
+
+    for (key, value) in values {
+        let column = table.columns.iter().find(|c| &c.name == key);
+
+        if let Value::Bytes(bytes) = value {
+            if bytes.len() > config.uploads.max_size {
+                invalid.push((key.to_owned(), format!("Files can be at most {} bytes", config.uploads.max_size)));
+            }
+            continue;
+        }
+
+        let text = match (column, value) {
+            (Some(column), Value::Text(text)) if column.is_json() && !text.is_empty() => text,
+            _ => continue,
+        };
+
+        let document = match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(document) => document,
+            Err(e) => {
+                invalid.push((key.to_owned(), format!("Invalid JSON: {e}")));
+                continue;
+            }
+        };
+
+        let schema = match table_config.and_then(|c| c.json_schema(key)) {
+            Some(schema) => schema,
+            None => continue,
+        };
+
+        match jsonschema::validator_for(&schema) {
+            Ok(validator) => {
+                for error in validator.iter_errors(&document) {
+                    let path = error.instance_path().to_string();
+                    let path = if path.is_empty() { "/".to_owned() } else { path };
+
+                    invalid.push((key.to_owned(), format!("{path}: {error}")));
+                }
+            }
+            Err(e) => {
+                invalid.push((key.to_owned(), format!("Invalid JSON Schema: {e}")));
+            }
+        }
+    }
+
+    match invalid.is_empty() {
+        true => Ok(()),
+        false => Err(Error::Invalid(invalid)),
+    }
+}
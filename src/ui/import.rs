@@ -0,0 +1,263 @@
+use crate::db::{self, import::{Import, Outcome}};
+use maud::{html, Markup, Render};
+
+/// How far away related records are followed when exporting a record, unless chosen
+pub const DEFAULT_GRAPH_DEPTH: usize = 2;
+pub const MAX_GRAPH_DEPTH: usize = 10;
+
+const DELIMITERS: [(&str, &str); 3] = [(",", "Comma"), ("\t", "Tab"), (";", "Semicolon")];
+
+fn import_url(table: &db::Table) -> String {
+    format!("/tables/{}/import", table.oid.0)
+}
+
+/// The token of the upload a mapping form was submitted for, by which the
+/// file itself is kept on the server
+pub fn submitted_upload(form: &[(String, String)]) -> Option<&str> {
+    form.iter().find(|(k, _)| k == "upload").map(|(_, v)| v.as_str())
+}
+
+/// Reads an import of an uploaded file back from the mapping form
+pub fn submitted(form: &[(String, String)], data: String) -> Result<Import, csv::Error> {
+    let get = |key: &str| form.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+
+    let delimiter = get("delimiter").and_then(|d| d.bytes().next()).unwrap_or(b',');
+
+    let mut import = Import::parse(data, delimiter)?;
+
+    for (i, column) in import.columns.iter_mut().enumerate() {
+        *column = get(&format!("column.{i}"))
+            .filter(|c| !c.is_empty())
+            .map(|c| c.to_owned());
+    }
+
+    import.key = get("key").filter(|k| !k.is_empty()).map(|k| k.to_owned());
+
+    Ok(import)
+}
+
+/// Choosing a file to import, and how its fields are separated
+pub struct Upload<'a> {
+    table: &'a db::Table,
+    error: Option<String>,
+}
+
+impl<'a, 'b: 'a> Upload<'a> {
+    pub fn new(table: &'b db::Table, error: Option<String>) -> Self {
+        Self { table, error }
+    }
+}
+
+impl<'a> Render for Upload<'a> {
+    fn render(&self) -> Markup {
+        html! {
+            c-form {
+                form method="post" action=(import_url(self.table)) enctype="multipart/form-data" {
+                    c-form-field {
+                        label.required for="file" { "file" }
+                        input id="file" name="file" type="file" accept=".csv,.tsv,.txt,text/csv" required;
+                    }
+                    c-form-field {
+                        label for="delimiter" { "delimiter" }
+                        select id="delimiter" name="delimiter" {
+                            @for (value, label) in DELIMITERS {
+                                option value=(value) { (label) }
+                            }
+                        }
+                    }
+                    c-form-controls {
+                        button type="submit" { "Upload" }
+                    }
+                    @if let Some(error) = &self.error {
+                        output class="error" {
+                            p { (error) }
+                        }
+                    }
+                }
+            }
+            c-form {
+                form method="post" action=(format!("{}/graph", import_url(self.table))) enctype="multipart/form-data" {
+                    c-form-field {
+                        label.required for="graph" { "record with related records (JSON)" }
+                        input id="graph" name="file" type="file" accept=".json,application/json" required;
+                    }
+                    c-form-controls {
+                        button type="submit" { "Import" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Exporting a record along with the records related to it, down to a chosen depth
+pub struct GraphExport<'a> {
+    table: &'a db::Table,
+    record_id: i64,
+}
+
+impl<'a, 'b: 'a> GraphExport<'a> {
+    pub fn new(table: &'b db::Table, record_id: i64) -> Self {
+        Self { table, record_id }
+    }
+}
+
+impl<'a> Render for GraphExport<'a> {
+    fn render(&self) -> Markup {
+        html! {
+            c-exports {
+                form method="get" action=(format!("/tables/{}/records/{}/export", self.table.oid.0, self.record_id)) {
+                    label for="depth" { "Export with related records, up to" }
+                    input id="depth" name="depth" type="number" min="0" max=(MAX_GRAPH_DEPTH) value=(DEFAULT_GRAPH_DEPTH);
+                    label for="depth" { "records away" }
+                    button type="submit" { "Export JSON" }
+                }
+            }
+        }
+    }
+}
+
+/// Mapping each of a file's fields onto a column, and choosing whether rows
+/// update existing records, along with the outcome of the last run, if any
+pub struct Mapping<'a> {
+    table: &'a db::Table,
+    import: &'a Import,
+    /// The token the uploaded file is kept by
+    upload: &'a str,
+    /// Columns that can be imported into, as (name, label) pairs
+    columns: Vec<(&'a str, &'a str)>,
+    outcome: Option<Outcome>,
+    error: Option<String>,
+}
+
+impl<'a, 'b: 'a> Mapping<'a> {
+    pub fn new(table: &'b db::Table, import: &'b Import, upload: &'b str, columns: Vec<(&'b str, &'b str)>) -> Self {
+        Self { table, import, upload, columns, outcome: None, error: None }
+    }
+
+    pub fn outcome(mut self, outcome: Outcome) -> Self {
+        self.outcome = Some(outcome);
+        self
+    }
+
+    pub fn error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    fn render_outcome(outcome: &Outcome) -> Markup {
+        let records = match outcome.imported {
+            1 => "1 record".to_owned(),
+            n => format!("{n} records"),
+        };
+
+        html! {
+            @if outcome.committed {
+                p { "Imported " (records) }
+            } @else if outcome.errors.is_empty() {
+                p { "Dry run: " (records) " would be imported" }
+            } @else {
+                p {
+                    "Nothing was imported, since " (outcome.errors.len()) " of the rows failed"
+                    " (" (records) " otherwise would have been)"
+                }
+                table {
+                    thead {
+                        tr {
+                            th { "Line" }
+                            th { "Error" }
+                            th { "Constraint" }
+                        }
+                    }
+                    tbody {
+                        @for error in &outcome.errors {
+                            tr {
+                                td { (error.line) }
+                                td { (error.message) }
+                                td {
+                                    @if let Some(constraint) = &error.constraint {
+                                        code { (constraint) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Render for Mapping<'a> {
+    fn render(&self) -> Markup {
+        let delimiter = (self.import.delimiter as char).to_string();
+
+        html! {
+            c-form.import {
+                form method="post" action=(format!("{}/run", import_url(self.table))) {
+                    input type="hidden" name="upload" value=(self.upload);
+                    input type="hidden" name="delimiter" value=(delimiter);
+                    table {
+                        thead {
+                            tr {
+                                th { "Field" }
+                                th { "Column" }
+                            }
+                        }
+                        tbody {
+                            @for (i, (header, column)) in self.import.headers.iter().zip(&self.import.columns).enumerate() {
+                                @let id = format!("column.{i}");
+
+                                tr {
+                                    td {
+                                        label for=(id) { (header) }
+                                    }
+                                    td {
+                                        select id=(id) name=(id) {
+                                            option value="" { "Ignore" }
+                                            @for (name, label) in &self.columns {
+                                                option value=(name) selected[column.as_deref() == Some(*name)] {
+                                                    (name)
+                                                    @if name != label {
+                                                        " (" (label) ")"
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    @if !self.table.unique_keys.is_empty() {
+                        c-form-field {
+                            label for="key" { "existing records" }
+                            select id="key" name="key" {
+                                option value="" { "Always insert new records" }
+                                @for key in self.table.unique_keys.iter() {
+                                    option value=(key.name) selected[self.import.key.as_ref() == Some(&key.name)] {
+                                        "Update records with the same " (key.columns.join(", "))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    c-form-controls {
+                        button type="submit" name="commit" value="false" { "Dry run" }
+                        button type="submit" name="commit" value="true" { "Import" }
+                    }
+                    @if let Some(error) = &self.error {
+                        output class="error" {
+                            p { (error) }
+                        }
+                    }
+                    @if let Some(outcome) = &self.outcome {
+                        output class=[(!outcome.errors.is_empty()).then_some("error")] {
+                            (Self::render_outcome(outcome))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use maud::{html, Markup, Render};
+
+/// Logging in, after which the user is sent back to where they were headed
+pub struct Login<'a> {
+    back: &'a str,
+    error: Option<&'a str>,
+}
+
+impl<'a, 'b: 'a> Login<'a> {
+    pub fn new(back: &'b str) -> Self {
+        Self { back, error: None }
+    }
+
+    pub fn error(mut self, error: &'b str) -> Self {
+        self.error = Some(error);
+        self
+    }
+}
+
+impl<'a> Render for Login<'a> {
+    fn render(&self) -> Markup {
+        html! {
+            c-form.login {
+                form method="post" action="/login" {
+                    input type="hidden" name="back" value=(self.back);
+                    c-form-field {
+                        label.required for="name" { "name" }
+                        input id="name" name="name" type="text" autocomplete="username" required autofocus;
+                    }
+                    c-form-field {
+                        label.required for="password" { "password" }
+                        input id="password" name="password" type="password" autocomplete="current-password" required;
+                    }
+                    c-form-controls {
+                        button type="submit" { "Log in" }
+                    }
+                    @if let Some(error) = self.error {
+                        output class="error" {
+                            p { (error) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,2 +1,6 @@
+pub mod actions;
 pub mod form;
+pub mod import;
+pub mod login;
 pub mod table;
+pub mod tree;
@@ -1,16 +1,23 @@
-use crate::db;
+use crate::{db, ui::actions::SELECTION_FORM};
 use maud::{html, Markup, Render};
 use sqlx::{Row, postgres::PgRow};
 
+/// Prefix of query params filtering the records list, eg. `filter.status=active`
+pub const FILTER_PREFIX: &str = "filter.";
+
 pub struct TableColumn {
+    attributes: Vec<String>,
     data_type: String,
+    element_type: Option<String>,
     name: String,
 }
 
 impl From<&db::Column> for TableColumn {
     fn from(column: &db::Column) -> Self {
         Self {
+            attributes: column.attributes.iter().map(|a| a.name.clone()).collect(),
             data_type: column.data_type.clone(),
+            element_type: column.element_type.clone(),
             name: column.name.clone(),
         }
     }
@@ -20,38 +27,130 @@ pub struct Table<'a> {
     table: &'a db::Table,
     columns: Vec<TableColumn>,
     rows: Vec<PgRow>,
+    /// Whether rows link to their records, which they don't if they aren't
+    /// records of the table, eg. the results of a function
+    linked: bool,
+    /// Whether rows can be selected, for bulk actions
+    selectable: bool,
 }
 
 impl<'a, 'b: 'a> Table<'a> {
     pub fn new(table: &'b db::Table, rows: Vec<PgRow>) -> Self {
         let columns = table.columns.iter()
-            .map(|c| TableColumn::from(c))
+            .map(TableColumn::from)
             .collect();
 
         Self {
             table,
             columns,
             rows,
+            linked: true,
+            selectable: false,
          }
     }
 
+    pub fn unlinked(mut self) -> Self {
+        self.linked = false;
+        self
+    }
+
+    /// Adds a checkbox to each row, submitted with the `ui::actions` selection form
+    pub fn selectable(mut self) -> Self {
+        self.selectable = true;
+        self
+    }
+
+    fn render_value(&self, column: &TableColumn, record_id: Option<&str>, value: &str) -> Markup {
+        let elements = match column.element_type {
+            Some(_) => db::literal::decode_array(value),
+            None => None,
+        };
+
+        let fields = match column.attributes.is_empty() {
+            true => None,
+            false => db::literal::decode_row(value),
+        };
+
+        html! {
+            @if let Some(fields) = fields {
+                dl.composite {
+                    @for (attribute, field) in column.attributes.iter().zip(fields) {
+                        dt { (attribute) }
+                        @match field {
+                            Some(field) => dd { (field) },
+                            None => dd.null { "NULL" },
+                        }
+                    }
+                }
+            } @else if let Some(elements) = elements {
+                ul.chips {
+                    @for element in elements {
+                        @match element {
+                            Some(element) => li { (element) },
+                            None => li.null { "NULL" },
+                        }
+                    }
+                }
+            } @else {
+                @match column.data_type.as_ref() {
+                    "bytea" => {
+                        @let url = record_id.map(|id| format!("/tables/{}/records/{}/files/{}", self.table.oid.0, id, column.name));
+
+                        @match (db::bytea::Summary::parse(value), url) {
+                            (Some(summary), Some(url)) => {
+                                @if summary.is_image() {
+                                    img.preview src=(format!("{url}?inline")) alt=(column.name);
+                                }
+                                a href=(url) { (summary.size) " bytes" }
+                            }
+                            // Files can only be served from records
+                            (Some(summary), None) => {
+                                (summary.size) " bytes"
+                            }
+                            (None, _) => code { (value) },
+                        }
+                    }
+                    "json" | "jsonb" => {
+                        @match serde_json::from_str::<serde_json::Value>(value) {
+                            Ok(document) => (render_json(&document)),
+                            Err(_) => code { (value) },
+                        }
+                    }
+                    "ltree" => {
+                        code { (value) }
+                    }
+                    _ => {
+                        (value)
+                    }
+                }
+            }
+        }
+    }
+
     fn render_row(&self, columns: &[TableColumn], row: &PgRow) -> Markup {
         html! {
-            @let record_id: String = row.try_get("id").unwrap();
+            // Views don't necessarily have an `id`, in which case their rows can't be opened
+            @let record_id: Option<String> = match self.linked {
+                true => row.try_get("id").ok().flatten(),
+                false => None,
+            };
+            @let table_oid = record_id.as_ref().map(|_| self.table.oid.0);
 
-            tr data-table-oid=(self.table.oid.0) data-record-id=(record_id) {
+            tr data-table-oid=[table_oid] data-record-id=[&record_id] {
+                @if self.selectable {
+                    td.select {
+                        @if let Some(record_id) = &record_id {
+                            input type="checkbox" name="id" value=(record_id) form=(SELECTION_FORM);
+                        }
+                    }
+                }
                 @for column in columns {
                     @let col_name: &str = column.name.as_ref();
-                    @let value: String = row.try_get(col_name).unwrap();
+                    @let value: Option<String> = row.try_get(col_name).unwrap();
 
                     td class=(&column.data_type) {
-                        @match column.data_type.as_ref() {
-                            "ltree" => {
-                                code { (value) }
-                            }
-                            _ => {
-                                (value)
-                            }
+                        @if let Some(value) = value {
+                            (self.render_value(column, record_id.as_deref(), &value))
                         }
                     }
                 }
@@ -60,6 +159,43 @@ impl<'a, 'b: 'a> Table<'a> {
     }
 }
 
+/// Renders a document as a tree of collapsed objects and arrays
+fn render_json(value: &serde_json::Value) -> Markup {
+    use serde_json::Value;
+
+    html! {
+        @match value {
+            Value::Object(object) => {
+                details.json {
+                    summary { "{…} " (object.len()) " keys" }
+                    dl {
+                        @for (key, value) in object {
+                            dt { (key) }
+                            dd { (render_json(value)) }
+                        }
+                    }
+                }
+            }
+            Value::Array(array) => {
+                details.json {
+                    summary { "[…] " (array.len()) " items" }
+                    ol start="0" {
+                        @for value in array {
+                            li { (render_json(value)) }
+                        }
+                    }
+                }
+            }
+            Value::String(string) => {
+                span.json-string { (string) }
+            }
+            _ => {
+                code { (value.to_string()) }
+            }
+        }
+    }
+}
+
 impl<'a> Render for Table<'a> {
     fn render(&self) -> Markup {
         // Places the table in a wrapper so that the wrapping container can be used
@@ -69,21 +205,133 @@ impl<'a> Render for Table<'a> {
                 table {
                     thead {
                         tr {
+                            @if self.selectable {
+                                th.select {
+                                    input type="checkbox" title="Select all" data-select-all;
+                                }
+                            }
                             @for column in &self.columns {
-                                th class=(column.data_type) data-column=(column.name) { (column.name) }
+                                // Only records can be sorted, by reloading them
+                                @let sort_column = self.linked.then_some(&column.name);
+
+                                th class=(column.data_type) data-column=[sort_column] { (column.name) }
                             }
                         }
                     }
                     tbody {
                         @for row in &self.rows {
-                            (self.render_row(self.columns.as_slice(), &row))
+                            (self.render_row(self.columns.as_slice(), row))
                         }
                     }
-                    caption {
-                        "Double-click any row to edit"
+                    @if self.linked {
+                        caption {
+                            @if self.table.updatable {
+                                "Double-click any row to edit"
+                            } @else {
+                                "Double-click any row to view"
+                            }
+                        }
                     }
                 }
             }
         }
     }
 }
+
+
+/// Links narrowing the records list down to each value of the table's enum
+/// columns, keeping any other params (sorting, other filters) in place
+pub struct Facets<'a> {
+    table: &'a db::Table,
+    query: &'a [(String, String)],
+}
+
+impl<'a, 'b: 'a> Facets<'a> {
+    pub fn new(table: &'b db::Table, query: &'b [(String, String)]) -> Self {
+        Self { table, query }
+    }
+
+    fn href(&self, key: &str, value: Option<&str>) -> String {
+        // Changing filters invalidates the current page
+        let mut params = self.query.iter()
+            .filter(|(k, _)| k != key && k != "page")
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>();
+
+        if let Some(value) = value {
+            params.push((key, value));
+        }
+
+        format!("?{}", serde_urlencoded::to_string(params).unwrap())
+    }
+}
+
+impl<'a> Render for Facets<'a> {
+    fn render(&self) -> Markup {
+        let columns = self.table.columns.iter()
+            .filter_map(|c| c.enum_values.as_ref().map(|values| (&c.name, values)))
+            .collect::<Vec<_>>();
+
+        html! {
+            @if !columns.is_empty() {
+                c-facets {
+                    @for (name, values) in columns {
+                        @let key = format!("{FILTER_PREFIX}{name}");
+                        @let current = self.query.iter().find(|(k, _)| k == &key).map(|(_, v)| v);
+
+                        section {
+                            h3 { (name) }
+                            menu {
+                                li {
+                                    a.current[current.is_none()] href=(self.href(&key, None)) { "All" }
+                                }
+                                @for value in values {
+                                    li {
+                                        a.current[current == Some(value)] href=(self.href(&key, Some(value))) {
+                                            (value)
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Links exporting the records list as files, keeping its sorting & filters
+pub struct Exports<'a> {
+    table: &'a db::Table,
+    query: &'a [(String, String)],
+}
+
+impl<'a, 'b: 'a> Exports<'a> {
+    pub fn new(table: &'b db::Table, query: &'b [(String, String)]) -> Self {
+        Self { table, query }
+    }
+
+    fn href(&self, format: &str) -> String {
+        let mut params = self.query.iter()
+            .filter(|(k, _)| k != "format" && k != "page")
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>();
+
+        params.push(("format", format));
+
+        format!("/tables/{}/records/export?{}", self.table.oid.0, serde_urlencoded::to_string(params).unwrap())
+    }
+}
+
+impl<'a> Render for Exports<'a> {
+    fn render(&self) -> Markup {
+        html! {
+            c-exports {
+                span { "Export:" }
+                a href=(self.href("csv")) download { "CSV" }
+                a href=(self.href("tsv")) download { "TSV" }
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::db::{self, tree::Node};
+use maud::{html, Markup, Render};
+
+/// The records of a table as a hierarchy, one level at a time; deeper levels
+/// are loaded as nodes are expanded
+pub struct Tree<'a> {
+    table: &'a db::Table,
+    column: &'a db::Column,
+    /// The nodes leading down to the current parent, as (key, label) pairs
+    ancestors: Vec<(String, String)>,
+    nodes: Vec<Node>,
+}
+
+impl<'a, 'b: 'a> Tree<'a> {
+    pub fn new(
+        table: &'b db::Table,
+        column: &'b db::Column,
+        ancestors: Vec<(String, String)>,
+        nodes: Vec<Node>,
+    ) -> Self {
+        Self { table, column, ancestors, nodes }
+    }
+
+    fn href(&self, parent: &str) -> String {
+        let params = serde_urlencoded::to_string([("parent", parent)]).unwrap();
+
+        format!("/tables/{}/tree/{}?{params}", self.table.oid.0, self.column.name)
+    }
+
+    /// Just the nodes below the parent, for expanding a node in place
+    pub fn nodes(&self) -> Markup {
+        html! {
+            ul {
+                @for node in &self.nodes {
+                    li {
+                        @if node.descendants > 0 {
+                            details data-children=(format!("{}&partial", self.href(&node.key))) {
+                                summary { (self.render_node(node)) }
+                            }
+                        } @else {
+                            (self.render_node(node))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn render_node(&self, node: &Node) -> Markup {
+        html! {
+            a.label href=(self.href(&node.key)) title=(node.key) { (node.label) }
+            @if node.descendants > 0 {
+                " "
+                small { (node.descendants) " below" }
+            }
+            @if let Some(record_id) = &node.record_id {
+                " "
+                a.edit href=(format!("/tables/{}/records/{}/edit", self.table.oid.0, record_id)) { "Edit" }
+            }
+        }
+    }
+}
+
+impl<'a> Render for Tree<'a> {
+    fn render(&self) -> Markup {
+        html! {
+            c-tree {
+                nav {
+                    a href=(self.href("")) { (self.column.name) }
+                    @for (key, label) in &self.ancestors {
+                        " / "
+                        a href=(self.href(key)) { (label) }
+                    }
+                }
+                @if self.nodes.is_empty() {
+                    p { "Nothing below here" }
+                } @else {
+                    (self.nodes())
+                }
+            }
+        }
+    }
+}
@@ -3,6 +3,13 @@
 function main() {
   setCurrentMenuLink();
   setCurrentMenuTab();
+  setupDefaultToggles();
+  setupArrayInputs();
+  setupUuidGenerators();
+  setupTreeNodes();
+  setupConfirmations();
+  setupSelection();
+  showFlash();
 
   let params = new URLSearchParams(window.location.search);
 
@@ -22,6 +29,8 @@ function main() {
   document.querySelectorAll('c-table thead th').forEach(th => {
     let column = th.getAttribute('data-column');
 
+    if (!column) { return; }
+
     if (params.get('sort_column') == column) {
       th.classList.add('sorted');
 
@@ -52,8 +61,119 @@ function toggleSortDirection(params) {
     : params.set('sort_direction', 'desc');
 }
 
+function setupDefaultToggles() {
+  // Inputs are disabled (and so not submitted) while their column is
+  // set to use its default value
+  document.querySelectorAll('c-form input[data-default-for]').forEach(checkbox => {
+    let input = document.getElementById(checkbox.getAttribute('data-default-for'));
+
+    if (!input) { return; }
+
+    checkbox.addEventListener('change', evt => {
+      input.disabled = checkbox.checked;
+    });
+  });
+}
+
+function setupArrayInputs() {
+  document.querySelectorAll('c-form fieldset.array').forEach(fieldset => {
+    let list = fieldset.querySelector('ol');
+    let template = fieldset.querySelector('template');
+
+    fieldset.addEventListener('click', evt => {
+      let action = evt.target.getAttribute('data-array-action');
+      let item = evt.target.closest('li');
+
+      switch (action) {
+        case 'add':
+          list.appendChild(template.content.cloneNode(true));
+          break;
+        case 'remove':
+          item.remove();
+          break;
+        case 'up':
+          item.previousElementSibling && list.insertBefore(item, item.previousElementSibling);
+          break;
+        case 'down':
+          item.nextElementSibling && list.insertBefore(item.nextElementSibling, item);
+          break;
+      }
+    });
+  });
+}
+
+function setupUuidGenerators() {
+  document.querySelectorAll('c-form button[data-generate-uuid]').forEach(button => {
+    let input = button.previousElementSibling;
+
+    button.addEventListener('click', evt => {
+      input.value = crypto.randomUUID();
+    });
+  });
+}
+
+function setupTreeNodes() {
+  // Children are only loaded once a node is first expanded, and since those
+  // can be expanded in turn, listen for toggles (which don't bubble) on the
+  // whole tree instead
+  document.querySelectorAll('c-tree').forEach(tree => {
+    tree.addEventListener('toggle', async evt => {
+      let details = evt.target;
+      let url = details.getAttribute('data-children');
+
+      if (!details.open || !url) { return; }
+
+      details.removeAttribute('data-children');
+
+      let response = await fetch(url);
+      details.insertAdjacentHTML('beforeend', await response.text());
+    }, true);
+  });
+}
+
+function setupConfirmations() {
+  // Either the form or the button submitting it can ask for confirmation
+  document.querySelectorAll('form').forEach(form => {
+    form.addEventListener('submit', evt => {
+      let message = evt.submitter?.getAttribute('data-confirm')
+        || form.getAttribute('data-confirm');
+
+      if (message && !confirm(message)) { evt.preventDefault(); }
+    });
+  });
+}
+
+function setupSelection() {
+  document.querySelectorAll('c-table input[data-select-all]').forEach(toggle => {
+    let checkboxes = toggle.closest('table').querySelectorAll('td.select input[type="checkbox"]');
+
+    toggle.addEventListener('change', evt => {
+      checkboxes.forEach(checkbox => { checkbox.checked = toggle.checked; });
+    });
+  });
+}
+
+function showFlash() {
+  // Set by the server for the page after a redirect, so it's only shown once
+  let cookie = document.cookie.split('; ').find(c => c.startsWith('flash='));
+
+  if (!cookie) { return; }
+
+  document.cookie = 'flash=; path=/; max-age=0';
+
+  let value = decodeURIComponent(cookie.slice('flash='.length));
+  let [level, ...message] = value.split(':');
+
+  let flash = document.createElement('c-flash');
+  flash.className = level;
+  flash.textContent = message.join(':');
+  flash.addEventListener('click', evt => flash.remove());
+
+  document.querySelector('c-content')?.prepend(flash);
+}
+
 function setCurrentMenuLink() {
-  let rgx = /^\/tables\/([0-9]+)/;
+  let rgx = /^\/(?:tables|functions)\/([0-9]+)/;
   let matches = window.location.pathname.match(rgx);
 
   if (!matches) { return; }
@@ -0,0 +1,11 @@
+-- Revision: band-status
+--
+-- Adds an enum-typed column to test select inputs and list filters
+
+begin;
+
+create type band_status as enum ('active', 'on hiatus', 'disbanded');
+
+alter table band add column status band_status not null default 'active';
+
+commit;
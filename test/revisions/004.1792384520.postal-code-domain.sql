@@ -0,0 +1,12 @@
+-- Revision: postal-code-domain
+--
+-- Adds a domain-typed column to test resolving domains to their base types
+
+begin;
+
+create domain us_postal_code as varchar(10)
+  check (value ~ '^\d{5}(-\d{4})?$');
+
+alter table city add column postal_code us_postal_code;
+
+commit;
@@ -0,0 +1,10 @@
+-- Revision: song-tags
+--
+-- Adds array columns to test repeatable inputs
+
+begin;
+
+alter table song add column tags text[] not null default '{}';
+alter table song add column track_lengths int[];
+
+commit;
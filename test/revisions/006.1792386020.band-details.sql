@@ -0,0 +1,9 @@
+-- Revision: band-details
+--
+-- Adds a jsonb column to test the JSON editor and schema validation
+
+begin;
+
+alter table band add column details jsonb;
+
+commit;
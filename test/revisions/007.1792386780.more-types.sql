@@ -0,0 +1,67 @@
+-- Revision: more-types
+--
+-- Adds columns of numeric, uuid, network, interval and range types
+-- to test their inputs
+
+begin;
+
+create table tour (
+  id
+    int
+    primary key
+    generated by default as identity,
+
+  public_id
+    uuid
+    unique
+    not null
+    default gen_random_uuid(),
+
+  band_id
+    int
+    not null
+    references band (id),
+
+  name
+    text
+    not null,
+
+  legs
+    int2
+    not null
+    default 1,
+
+  ticket_price
+    numeric(6, 2),
+
+  revenue
+    money,
+
+  rating
+    float8,
+
+  doors_open
+    time,
+
+  announced_at
+    timestamp,
+
+  length
+    interval,
+
+  dates
+    daterange
+    not null,
+
+  capacity
+    int4range,
+
+  booking_host
+    inet,
+
+  booking_mac
+    macaddr
+);
+comment on table tour is 'A run of concerts by a band';
+
+commit;
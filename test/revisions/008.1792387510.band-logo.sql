@@ -0,0 +1,9 @@
+-- Revision: band-logo
+--
+-- Adds a bytea column to test file uploads & downloads
+
+begin;
+
+alter table band add column logo bytea;
+
+commit;
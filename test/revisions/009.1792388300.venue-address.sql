@@ -0,0 +1,35 @@
+-- Revision: venue-address
+--
+-- Adds a composite-type column (itself containing a composite) to test
+-- nested fieldsets
+
+begin;
+
+create type coordinates as (
+  latitude  numeric(8, 6),
+  longitude numeric(9, 6)
+);
+
+create type address as (
+  street      text,
+  city        text,
+  postal_code us_postal_code,
+  location    coordinates
+);
+
+create table venue (
+  id
+    int
+    primary key
+    generated always as identity,
+
+  name
+    text
+    not null,
+
+  address
+    address
+);
+comment on table venue is 'A place where concerts are held';
+
+commit;
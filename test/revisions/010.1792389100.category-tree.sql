@@ -0,0 +1,32 @@
+-- Revision: category-tree
+--
+-- Adds a table referencing itself, to test browsing & re-parenting
+-- hierarchies of records
+
+begin;
+
+create table category (
+  id
+    int
+    primary key
+    generated always as identity,
+
+  parent_id
+    int
+    references category(id),
+
+  name
+    text
+    not null
+);
+comment on table category is 'A nested grouping of songs, eg. for playlists';
+
+insert into category (parent_id, name) values
+  (null, 'Moods'),
+  (1,    'Upbeat'),
+  (1,    'Mellow'),
+  (2,    'Workout'),
+  (null, 'Occasions'),
+  (5,    'Road trip');
+
+commit;
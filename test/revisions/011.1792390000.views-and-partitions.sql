@@ -0,0 +1,50 @@
+-- Revision: views-and-partitions
+--
+-- Adds relations other than plain tables: an auto-updatable view, a view
+-- that can't be updated, a materialized view, and a partitioned table
+
+begin;
+
+create view active_band as
+  select id, name, status
+  from band
+  where status = 'active'
+  with check option;
+comment on view active_band is 'Bands still together, editable through the view';
+
+create view band_size as
+  select band.id, band.name, count(band_member.id) as members
+  from band
+  left join band_member on band_member.band_id = band.id
+  group by band.id;
+comment on view band_size is 'How many members each band has had';
+
+create materialized view band_size_snapshot as
+  select * from band_size;
+
+create table play (
+  id
+    int
+    generated always as identity,
+
+  song_id
+    int
+    not null
+    references song(id),
+
+  played_on
+    date
+    not null
+    default current_date,
+
+  primary key (id, played_on)
+) partition by range (played_on);
+comment on table play is 'A song being played, partitioned by year';
+
+create table play_2025 partition of play
+  for values from ('2025-01-01') to ('2026-01-01');
+
+create table play_2026 partition of play
+  for values from ('2026-01-01') to ('2027-01-01');
+
+commit;
@@ -0,0 +1,55 @@
+-- Revision: band-functions
+--
+-- Adds functions to test calling them as actions, returning a row, a set
+-- of rows, a single value, and nothing at all
+
+begin;
+
+create function merge_bands(keep int, merge int) returns band
+language plpgsql
+as $$
+declare
+  kept band;
+begin
+  if keep = merge then
+    raise exception 'Can''t merge a band into itself';
+  end if;
+
+  update band_member set band_id = keep where band_id = merge;
+  update song_artist set band_id = keep where band_id = merge;
+  update tour set band_id = keep where band_id = merge;
+  delete from band where id = merge;
+
+  select * into strict kept from band where id = keep;
+  return kept;
+end;
+$$;
+comment on function merge_bands is 'Moves members, songs & tours of one band to another, then deletes it';
+
+create function songs_tagged(tag text, max_results int default 10)
+returns table (id int, name text, released date)
+language sql
+stable
+as $$
+  select song.id, song.name, song.released
+  from song
+  where tag = any(song.tags)
+  order by song.released
+  limit max_results;
+$$;
+comment on function songs_tagged is 'Songs with a tag, oldest first';
+
+create function band_count(status band_status default 'active') returns bigint
+language sql
+stable
+as $$
+  select count(*) from band where band.status = band_count.status;
+$$;
+
+create function touch_band(id int) returns void
+language sql
+as $$
+  update band set added_on = now() where band.id = touch_band.id;
+$$;
+
+commit;
@@ -0,0 +1,19 @@
+-- Revision: deactivate-member
+--
+-- Adds a function to test custom actions on records
+
+begin;
+
+create function deactivate_member(member_id int) returns void
+language plpgsql
+as $$
+begin
+  update band_member set active = false where id = member_id and active;
+
+  if not found then
+    raise exception 'Member % is already inactive', member_id;
+  end if;
+end;
+$$;
+
+commit;
@@ -0,0 +1,22 @@
+-- Revision: viewer-role
+--
+-- Adds a role that can only read, to test users acting as a role whose
+-- privileges the database enforces
+
+begin;
+
+do $$
+begin
+  if not exists (select from pg_roles where rolname = 'mngr_viewer') then
+    create role mngr_viewer nologin;
+  end if;
+end;
+$$;
+
+-- The user connecting to the database acts as this role, so must be a member
+grant mngr_viewer to current_user;
+
+grant usage on schema public, ext to mngr_viewer;
+grant select on all tables in schema public to mngr_viewer;
+
+commit;
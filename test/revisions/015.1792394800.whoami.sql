@@ -0,0 +1,17 @@
+-- Revision: whoami
+--
+-- Adds a function reading the session variables mngr sets for the logged in
+-- user, the way row-level security policies & audit triggers would
+
+begin;
+
+create function whoami(out user_name text, out user_id text)
+language sql
+stable
+as $$
+  select
+    current_setting('app.user_name', true),
+    current_setting('app.user_id', true);
+$$;
+
+commit;
@@ -0,0 +1,11 @@
+-- Revision: viewer-privileges
+--
+-- Lets the viewer rename bands, but nothing else, and hides the categories
+-- from them, to test hiding what a role's privileges don't allow
+
+begin;
+
+grant update (name) on band to mngr_viewer;
+revoke select on category from mngr_viewer;
+
+commit;